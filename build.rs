@@ -0,0 +1,112 @@
+//! Precompress the client assets which are embedded in the binaries and compute their ETags,
+//! so the server doesn't have to do it on every request.
+//!
+//! This generates `assets.rs` in the `OUT_DIR` which is included in `serve_files.rs`.
+//! It also checks that the release build of the desktop app has the public key of the licenses.
+use sha2::{Digest, Sha256};
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// the url path, the file relative to the crate and its content type
+const ASSETS: [(&str, &str, &str); 4] = [
+    ("/favicon.ico", "favicon.ico", "image/x-icon"),
+    (
+        "/pkg/client.js",
+        "client/pkg/client.js",
+        "text/javascript; charset=UTF-8",
+    ),
+    (
+        "/pkg/client_bg.wasm",
+        "client/pkg/client_bg.wasm",
+        "application/wasm",
+    ),
+    (
+        "/assets/JuliaMono-Light.woff2",
+        "assets/JuliaMono-Light.woff2",
+        "font/woff2",
+    ),
+];
+
+/// the base64 of the public key which verifies the licenses, see `license.rs`
+const LICENSE_PUBLIC_KEY: &str = "MADNOTES_LICENSE_PUBLIC_KEY";
+
+fn main() -> io::Result<()> {
+    let manifest_dir = PathBuf::from(env::var("CARGO_MANIFEST_DIR").expect("set by cargo"));
+    let out_dir = PathBuf::from(env::var("OUT_DIR").expect("set by cargo"));
+
+    println!("cargo:rerun-if-changed=build.rs");
+    check_license_key()?;
+    let mut generated = String::from("pub(crate) static ASSETS: &[Asset] = &[\n");
+    for (url, file, content_type) in ASSETS.iter() {
+        let source = manifest_dir.join(file);
+        println!("cargo:rerun-if-changed={}", source.display());
+        let content = fs::read(&source)?;
+
+        let name = file.replace('/', "_");
+        let gzip_file = out_dir.join(format!("{}.gz", name));
+        fs::write(&gzip_file, gzip(&content)?)?;
+        let brotli_file = out_dir.join(format!("{}.br", name));
+        fs::write(&brotli_file, brotli(&content)?)?;
+
+        generated += &format!(
+            "    Asset {{\n        path: {:?},\n        content_type: {:?},\n        hash: {:?},\n        identity: include_bytes!({:?}),\n        gzip: include_bytes!({:?}),\n        brotli: include_bytes!({:?}),\n    }},\n",
+            url,
+            content_type,
+            hash(&content),
+            path_str(&source),
+            path_str(&gzip_file),
+            path_str(&brotli_file),
+        );
+    }
+    generated += "];\n";
+    fs::write(out_dir.join("assets.rs"), generated)?;
+    Ok(())
+}
+
+/// There is no fallback key, so a released desktop app must be built with the key of the issuer
+fn check_license_key() -> io::Result<()> {
+    println!("cargo:rerun-if-env-changed={}", LICENSE_PUBLIC_KEY);
+    let is_release = env::var("PROFILE").map_or(false, |profile| profile == "release");
+    let is_desktop = env::var_os("CARGO_FEATURE_DESKTOP_APP").is_some();
+    if is_release && is_desktop && env::var_os(LICENSE_PUBLIC_KEY).is_none() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!(
+                "{} must be set to the public key of the license issuer in the release build",
+                LICENSE_PUBLIC_KEY
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// the hex of the first 16 bytes of the sha2-256 digest, enough to tell the builds apart
+fn hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .take(16)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+fn gzip(content: &[u8]) -> io::Result<Vec<u8>> {
+    let mut encoder = flate2::write::GzEncoder::new(vec![], flate2::Compression::best());
+    encoder.write_all(content)?;
+    encoder.finish()
+}
+
+fn brotli(content: &[u8]) -> io::Result<Vec<u8>> {
+    let mut compressed = vec![];
+    {
+        // buffer size, quality and window size
+        let mut encoder = brotli::CompressorWriter::new(&mut compressed, 4096, 11, 22);
+        encoder.write_all(content)?;
+    }
+    Ok(compressed)
+}
+
+fn path_str(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
@@ -2,136 +2,176 @@
 // A callback function is stored in callback_pool where the index is and the request is passed
 // as Json string to the main rust program which is then intercepted in the invoke_handler.
 //
+// The request carries the method, url, headers and the body encoded in base64.
+//
 // When the rust code has finish the response it will call on `responseCallback` via eval.
-// The callback_id is also passed and the response_payload.
+// The callback_id is also passed and the response, which is a json string containing
+// the status, the headers and the body encoded in base64.
 
 var callback_pool = [];
 var callback_pool_free = [];
 
+// callback_id is needed to determine which function to call
+// when there is still an executing request in action
+function register_to_callback_pool(callbackFn){
+        let callback_id =  callback_pool_free.pop();
+        if (callback_id !== undefined) {
+            callback_pool[callback_id] = callbackFn;
+            return callback_id;
+        }else{
+            let callback_id = callback_pool.push(callbackFn) - 1;
+            return callback_id;
+        }
+}
+
+/// called from the rust side using eval
+//  data is the response in json string
+function responseCallback(callback_id, data){
+    let callbackFn = callback_pool[callback_id];
+    callback_pool[callback_id] = null;
+    callback_pool_free.push(callback_id);
+    callbackFn(JSON.parse(data));
+}
+
+function bytes_to_base64(bytes){
+    let binary = "";
+    for (let i = 0; i < bytes.length; i++) {
+        binary += String.fromCharCode(bytes[i]);
+    }
+    return window.btoa(binary);
+}
+
+function base64_to_bytes(encoded){
+    let binary = window.atob(encoded);
+    let bytes = new Uint8Array(new ArrayBuffer(binary.length));
+    for (let i = 0; i < binary.length; i++) {
+        bytes[i] = binary.charCodeAt(i);
+    }
+    return bytes;
+}
+
+/// the body of the request as bytes, only the types used by the client are supported
+function body_to_bytes(body){
+    if (body === undefined || body === null) {
+        return Promise.resolve(null);
+    } else if (typeof body === "string") {
+        return Promise.resolve(new TextEncoder().encode(body));
+    } else if (body instanceof ArrayBuffer) {
+        return Promise.resolve(new Uint8Array(body));
+    } else if (ArrayBuffer.isView(body)) {
+        return Promise.resolve(new Uint8Array(body.buffer, body.byteOffset, body.byteLength));
+    } else if (body instanceof Blob) {
+        return body.arrayBuffer().then(function(buffer){ return new Uint8Array(buffer); });
+    } else {
+        return Promise.resolve(new TextEncoder().encode(String(body)));
+    }
+}
+
+/// send the request to the rust side, resolves with {status, headers, body}
+//  where headers is an array of [name, value] and body is the bytes
+function bridge_request(method, url, headers, body){
+    return body_to_bytes(body).then(function(bytes){
+        return new Promise(function(resolve){
+            let callback_id = register_to_callback_pool(function(response){
+                resolve({
+                    status: response.status,
+                    headers: response.headers,
+                    body: base64_to_bytes(response.body)
+                });
+            });
+            let arg = {
+                method: method.toUpperCase(),
+                url: url,
+                callback_id: callback_id,
+                headers: headers,
+                body: bytes ? bytes_to_base64(bytes) : null
+            };
+            window.webkit.messageHandlers.external.postMessage(JSON.stringify(arg));
+        });
+    });
+}
+
 (function(XHR) {
     "use strict";
-    
-    var stats = [];
-    var timeoutId = null;
-    
-    var open = XHR.prototype.open;
-    var send = XHR.prototype.send;
-    var headers = {};
-    
+
     XHR.prototype.open = function(method, url, async, user, pass) {
-        console.log("XHR intercepting.. ", url);
         this._url = url;
         this._method = method;
-        open.call(this, method, url, async, user, pass);
+        this._headers = {};
+        this._response_headers = [];
     };
 
     XHR.prototype.setRequestHeader = function(header, value) {
-        console.log("setting header: "+header+" = ", value);
-        headers[header] = value;
+        this._headers[header] = value;
+    };
+
+    XHR.prototype.getResponseHeader = function(name) {
+        let header = this._response_headers.find(function(header){
+            return header[0].toLowerCase() === name.toLowerCase();
+        });
+        return header ? header[1] : null;
+    };
+
+    XHR.prototype.getAllResponseHeaders = function() {
+        return this._response_headers.map(function(header){
+            return header[0] + ": " + header[1] + "\r\n";
+        }).join("");
     };
-    
+
     XHR.prototype.send = function(data) {
-        console.log("data is trying to be sent..", data);
         var self = this;
-        var start;
-        var oldOnReadyStateChange;
-        var url = this._url;
-        var method = this._method;
-
-        function onReadyCallbackFn(arg_resp) {
-            open.call(self, method, url, false, null, null);
-            console.log("this is called");
+        bridge_request(this._method, this._url, this._headers, data).then(function(response){
+            self._response_headers = response.headers;
+            let text = new TextDecoder().decode(response.body);
             Object.defineProperty(self, "readyState", {writable: true});
             Object.defineProperty(self, "status", {writable: true});
             Object.defineProperty(self, "response", {writable: true});
             Object.defineProperty(self, "responseText", {writable: true});
-            self.responseText = callbackResponse.body
-            self.response = callbackResponse.body
-            self.readyState = 4;
-            self.status = callbackResponse.code;
-            var event; // this is the load event 
-
-            if (document.createEvent) {
-              event = document.createEvent("HTMLEvents");
-              event.initEvent("load", true, true);
+            self.status = response.status;
+            self.responseText = text;
+            if (self.responseType === "arraybuffer") {
+                self.response = response.body.buffer;
+            } else if (self.responseType === "json") {
+                self.response = JSON.parse(text);
             } else {
-              event = document.createEventObject();
-              event.eventType = "load";
+                self.response = text;
             }
+            self.readyState = 4;
 
-            event.eventName = "load";
-            //trigger the load event here
-            if (document.createEvent) {
-              self.dispatchEvent(event);
-            } else {
-              self.fireEvent("on" + event.eventType, event);
-            }
-        }
-        
-        /// register to callback pool expecting 1 argument
-        let callback_id = register_to_callback_pool(onReadyCallbackFn);
-        let arg = {
-            method: method,
-            body: data,
-            url: url, 
-            callback_id: callback_id,
-            headers: headers
-        };
-        let arg_str = JSON.stringify(arg);
-        window.webkit.messageHandlers.external.postMessage(arg_str);
-    }
+            ["readystatechange", "load", "loadend"].forEach(function(name){
+                let event = document.createEvent("HTMLEvents");
+                event.initEvent(name, true, true);
+                self.dispatchEvent(event);
+            });
+        });
+    };
 })(XMLHttpRequest);
 
 
-
-// callback_id is needed to determine which function to call
-// when there is still an executing request in action
-function register_to_callback_pool(callbackFn){
-        let callback_id =  callback_pool_free.pop();
-        if (callback_id) {
-            callback_pool[callback_id] = callbackFn;
-            return callback_id;
-        }else{
-            let callback_id = callback_pool.push(callbackFn) - 1;
-            return callback_id;
-        }
-}
-
-/// called from the rust side using eval
-//  data is the argument in json string
-function responseCallback(callback_id, data){
-    let callbackFn = callback_pool[callback_id];
-    callbackFn(data);
-    callback_pool_free.push(callback_id);
-}
-
-
 // hijack fetch function
-window.fetch = function(url){
-    return new Promise(function(resolve,reject){
-        /// resolve is will be called after the rust code executes
-        //  decode base64 then convert to Uint8Array
-        function decode_wasm_b64_and_resolve(wasm_b64){
-            let wasm = window.atob(wasm_b64);
-            var rawLength = wasm.length;
-            var array = new Uint8Array(new ArrayBuffer(rawLength));
-
-            for(i = 0; i < rawLength; i++) {
-               array[i] = wasm.charCodeAt(i);
-            }
-            resolve(array);
-        }
+window.fetch = function(input, init){
+    init = init || {};
+    let request = (typeof input === "string" || input instanceof URL) ? null : input;
+    let url = request ? request.url : String(input);
+    let method = init.method || (request ? request.method : "GET");
 
-        let callback_id = register_to_callback_pool(decode_wasm_b64_and_resolve);
-        let arg = {
-                url,
-                callback_id, 
-                method:"fetch"
-            };
-        window.webkit.messageHandlers.external.postMessage(JSON.stringify(arg));
-    })
-}
+    let headers = {};
+    new Headers(init.headers || (request ? request.headers : {})).forEach(function(value, name){
+        headers[name] = value;
+    });
 
-function dummy(arg){
-    console.log("dummy function is called with arg: {}", JSON.stringify(arg));
+    let body = init.body;
+    if (body === undefined && request && method !== "GET" && method !== "HEAD") {
+        body = request.arrayBuffer();
+    }
+    return Promise.resolve(body).then(function(body){
+        return bridge_request(method, url, headers, body);
+    }).then(function(response){
+        // these statuses must not have a body
+        let no_body = [101, 204, 205, 304].indexOf(response.status) >= 0;
+        return new Response(no_body ? null : response.body, {
+            status: response.status,
+            headers: response.headers
+        });
+    });
 }
@@ -1,3 +1,29 @@
 function invoke(x){
     window.webkit.messageHandlers.external.postMessage(x);
 }
+
+// The callbacks of the commands which are waiting for the response of the desktop app,
+// keyed by the callback_id which is passed along with the command.
+var command_callbacks = {};
+var next_command_callback_id = 0;
+
+/// called from the wasm client, the command is a json string
+function invoke_command(command, callback){
+    let callback_id = next_command_callback_id++;
+    command_callbacks[callback_id] = callback;
+    let invocation = {
+        callback_id: callback_id,
+        command: JSON.parse(command)
+    };
+    invoke(JSON.stringify(invocation));
+}
+
+/// called from the rust side using eval
+//  response is the json string of the command result
+function commandCallback(callback_id, response){
+    let callbackFn = command_callbacks[callback_id];
+    delete command_callbacks[callback_id];
+    if (callbackFn) {
+        callbackFn(response);
+    }
+}
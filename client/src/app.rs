@@ -1,16 +1,47 @@
+#[cfg(feature = "external-invoke")]
+use crate::Command;
+use crate::{CommandOutput, CommandResult, NoteEntry, NoteEvent, NoteVersion, Snapshot};
+use about::About;
+use conflict::Conflict;
+use http::NoteResponse;
 use menu::Menu;
 use menu::MenuAction;
+use note_picker::NotePicker;
+use recovery::Recovery;
 use rendered_markdown::RenderedMarkdown;
 use sauron::jss::jss;
 use sauron::prelude::*;
 use sauron::Window;
+use scroll_sync::Pane;
+use std::cell::Cell;
+use std::rc::Rc;
 use ultron::editor;
 use ultron::editor::Editor;
 use ultron::nalgebra::Point2;
 
+mod about;
 mod assets;
+mod conflict;
+mod dialog;
+mod diff;
+mod http;
+#[cfg(feature = "external-invoke")]
+mod invoke;
 mod menu;
+mod note_picker;
+mod recovery;
 mod rendered_markdown;
+mod scroll_sync;
+
+/// render the markdown into a static node, this doesn't need the wasm runtime
+pub(crate) fn render_markdown<MSG>(content: &str) -> Node<MSG> {
+    rendered_markdown::render_static(content, &rendered_markdown::Config::default())
+}
+
+/// the styles needed by the markdown rendered with `render_markdown`
+pub(crate) fn markdown_style() -> String {
+    rendered_markdown::style()
+}
 
 pub(crate) enum Msg {
     EditorMsg(editor::Msg),
@@ -22,15 +53,88 @@ pub(crate) enum Msg {
     WindowMousemove(i32, i32),
     SeparatorDragStart(i32, i32),
     EditorScrolled((i32, i32)),
+    PreviewScrolled((i32, i32)),
+    /// the vertical position of the click in the rendered markdown,
+    /// None when an interactive element is clicked
+    PreviewClicked(Option<i32>),
     OpenFileClicked,
     RenderedMarkdownMsg(rendered_markdown::Msg),
+    /// the note which was requested and the response of the server
+    NoteLoaded(String, Result<NoteResponse, String>),
+    NoteEvent(NoteEvent),
+    FileOpened(CommandResult),
+    NotesListed(Result<String, String>),
+    NotePickerMsg(note_picker::Msg),
+    NotePicked(Option<String>),
+    /// the content which was saved and the result of the save command
+    FileSaved(String, CommandResult),
+    /// the note, the content which was saved and the response of the server
+    NoteSaved(String, String, Result<NoteResponse, String>),
+    CommandDone(CommandResult),
+    RecentListed(Result<Vec<String>, String>),
+    AboutMsg(about::Msg),
+    AboutEvent(about::Event),
+    LicenseStatus(CommandResult),
+    LicenseInstalled(CommandResult),
+    SnapshotsListed(Result<String, String>),
+    /// the note and its content on disk, which the recovered snapshot is compared to
+    DiskContentLoaded(String, Result<DiskNote, String>),
+    RecoveryMsg(recovery::Msg),
+    RecoveryEvent(recovery::Event),
+    AutosaveTick,
+    AutosaveDone(Result<String, String>),
+    /// check if the note was modified by another program while the app was in the background
+    WindowFocused,
+    /// the desktop app is sent the unsaved changes, the window may be closed next
+    WindowBlurred,
+    /// The number of edits when the typing paused, it is outdated if there were more edits since.
+    /// The markdown is rendered again once the typing pauses, rather than on every edit.
+    TypingPaused(u32),
+    DiskNoteChecked(String, Result<DiskNote, String>),
+    /// the note on disk after the save was refused since it was modified
+    SaveConflict(String, Result<DiskNote, String>),
+    ConflictMsg(conflict::Msg),
+    ConflictEvent(conflict::Event),
 }
 
+/// the content of a note on disk and its version
+type DiskNote = (String, Option<NoteVersion>);
+
 pub struct App {
+    /// the path of the note being edited, relative to the workspace.
+    /// It is only set once the note is loaded, so a failed load is never saved over the note.
+    note: Option<String>,
+    /// the note to open at startup, which becomes the note being edited once it is loaded
+    initial_note: Option<String>,
+    /// the current content of the editor
+    content: String,
+    /// the content when the note was last loaded or saved
+    saved_content: String,
+    /// the version of the note on disk when it was last loaded or saved
+    version: Option<NoteVersion>,
+    /// shared with the `beforeunload` listener, which can not access the app
+    is_dirty: Rc<Cell<bool>>,
     editor: Editor<Msg>,
     rendered_markdown: RenderedMarkdown<Msg>,
     editor_scroll: Point2<i32>,
+    /// the pane which was scrolled to align it with the other one and its new scroll top,
+    /// so its scroll event is not synced back
+    synced_scroll: Option<(Pane, i32)>,
     menu: Menu<Msg>,
+    /// shown when opening a note in the server mode
+    note_picker: Option<NotePicker<Msg>>,
+    /// shown from Help > About
+    about: Option<About<Msg>>,
+    /// shown at startup when there are unsaved buffers from the last session
+    recovery: Option<Recovery<Msg>>,
+    /// the id of the snapshots taken in this session
+    session: String,
+    /// the note and the content of the last snapshot of the buffer
+    autosaved: Option<(Option<String>, String)>,
+    /// the number of edits of the buffer, to tell when the typing paused
+    edits: u32,
+    /// shown when the note was modified on disk while it has unsaved changes
+    conflict: Option<Conflict<Msg>>,
     separator: Separator,
 }
 
@@ -51,6 +155,38 @@ impl Default for Separator {
 
 impl App {
     pub fn with_content(content: &str) -> Self {
+        Self {
+            note: None,
+            initial_note: None,
+            content: content.to_string(),
+            saved_content: content.to_string(),
+            version: None,
+            is_dirty: Rc::new(Cell::new(false)),
+            editor: Self::create_editor(content),
+            rendered_markdown: RenderedMarkdown::from_str(content),
+            editor_scroll: Point2::new(0, 0),
+            synced_scroll: None,
+            menu: Menu::default().on_activate(|menu_action| Msg::MenuAction(menu_action)),
+            note_picker: None,
+            about: None,
+            recovery: None,
+            session: String::new(),
+            autosaved: None,
+            edits: 0,
+            conflict: None,
+            separator: Separator::default(),
+        }
+    }
+
+    /// start with an empty content, the note is then loaded from the server
+    pub fn with_note(note: &str) -> Self {
+        Self {
+            initial_note: Some(note.to_string()),
+            ..Self::with_content("")
+        }
+    }
+
+    fn create_editor(content: &str) -> Editor<Msg> {
         let options = ultron::Options {
             use_block_mode: false,
             show_line_numbers: false,
@@ -59,14 +195,412 @@ impl App {
             syntax_token: "md".to_string(),
             ..Default::default()
         };
-        Self {
-            editor: Editor::from_str(options.clone(), content).on_change(Msg::EditorContentChanged),
-            rendered_markdown: RenderedMarkdown::from_str(content),
-            editor_scroll: Point2::new(0, 0),
-            menu: Menu::default().on_activate(|menu_action| Msg::MenuAction(menu_action)),
-            separator: Separator::default(),
+        Editor::from_str(options, content).on_change(Msg::EditorContentChanged)
+    }
+
+    /// replace the content of both the editor and the rendered markdown
+    fn set_content(&mut self, content: &str) {
+        self.content = content.to_string();
+        self.saved_content = content.to_string();
+        self.is_dirty.set(false);
+        self.editor = Self::create_editor(content);
+        // the files referenced by the note are served relative to the workspace
+        #[cfg(not(feature = "external-invoke"))]
+        self.rendered_markdown.set_note_dir(
+            self.note
+                .as_deref()
+                .and_then(|note| note.rsplit_once('/'))
+                .map(|(dir, _)| dir)
+                .unwrap_or_default(),
+        );
+        self.rendered_markdown
+            .update(rendered_markdown::Msg::NoteLoaded(content.to_string()));
+    }
+
+    /// the note of the desktop app is a path, such as the file passed on the command line
+    #[cfg(feature = "external-invoke")]
+    fn open_note(&self, path: String) -> Cmd<Self, Msg> {
+        invoke::invoke_command(Command::OpenPath { path }, Msg::FileOpened)
+    }
+
+    #[cfg(not(feature = "external-invoke"))]
+    fn open_note(&self, note: String) -> Cmd<Self, Msg> {
+        http::fetch_note(
+            "GET",
+            &http::open_note_url(&note),
+            None,
+            None,
+            move |response| Msg::NoteLoaded(note.clone(), response),
+        )
+    }
+
+    fn is_dirty(&self) -> bool {
+        self.content != self.saved_content
+    }
+
+    /// show the name of the note in the title, marked with `*` when there are unsaved changes
+    fn set_title(&self) -> Cmd<Self, Msg> {
+        let name = self.note.as_deref().map(file_name).unwrap_or(UNTITLED);
+        let title = if self.is_dirty() {
+            format!("* {}", name)
+        } else {
+            name.to_string()
+        };
+        sauron::document().set_title(&title);
+        // the document title is not shown in the desktop app
+        #[cfg(feature = "external-invoke")]
+        return Cmd::batch(vec![
+            invoke::invoke_command(Command::SetTitle { title }, Msg::CommandDone),
+            self.report_unsaved(),
+        ]);
+        #[cfg(not(feature = "external-invoke"))]
+        Cmd::none()
+    }
+
+    /// update the title only when the dirty state changes, since this is called on every edit
+    fn update_dirty_state(&mut self) -> Cmd<Self, Msg> {
+        self.edits = self.edits.wrapping_add(1);
+        let is_dirty = self.is_dirty();
+        let title = if is_dirty != self.is_dirty.get() {
+            self.is_dirty.set(is_dirty);
+            self.set_title()
+        } else {
+            Cmd::none()
+        };
+        // the markdown is parsed again and the desktop app is sent the latest changes only
+        // once the typing pauses, rather than on every edit
+        Cmd::batch(vec![
+            title,
+            recovery::after(TYPING_PAUSE, {
+                let edits = self.edits;
+                move || Msg::TypingPaused(edits)
+            }),
+        ])
+    }
+
+    /// Render the content of the editor, together with the source map used by the scroll sync.
+    /// The content is only parsed once the typing pauses.
+    fn typing_paused(&mut self) -> Cmd<Self, Msg> {
+        let effects = self
+            .rendered_markdown
+            .update(rendered_markdown::Msg::ContentChanged(self.content.clone()));
+        #[cfg(feature = "external-invoke")]
+        if self.is_dirty() {
+            return self.report_unsaved();
+        }
+        Cmd::none()
+    }
+
+    /// The desktop app is sent the unsaved changes, which it offers to save when its window is
+    /// closed. The browser is warned by the `beforeunload` listener instead.
+    /// They are sent when the buffer becomes dirty or is saved, and when the typing pauses or the
+    /// window loses the focus.
+    #[cfg(feature = "external-invoke")]
+    fn report_unsaved(&self) -> Cmd<Self, Msg> {
+        let content = if self.is_dirty() {
+            Some(self.content.clone())
+        } else {
+            None
+        };
+        let command = Command::Unsaved {
+            session: self.session.clone(),
+            note: self.note.clone(),
+            content,
+            version: self.version.clone(),
+        };
+        invoke::invoke_command(command, Msg::CommandDone)
+    }
+
+    fn content_saved(
+        &mut self,
+        note: String,
+        content: String,
+        version: Option<NoteVersion>,
+    ) -> Cmd<Self, Msg> {
+        self.note = Some(note);
+        self.saved_content = content;
+        self.version = version;
+        self.is_dirty.set(self.is_dirty());
+        Cmd::batch(vec![self.set_title(), self.load_recent(), self.autosave()])
+    }
+
+    /// Store a snapshot of the buffer when it has changed since the last one.
+    /// The snapshot is removed once the buffer is saved, or when it is replaced by another note.
+    fn autosave(&mut self) -> Cmd<Self, Msg> {
+        let mut cmds = vec![];
+        if let Some((note, _)) = &self.autosaved {
+            if *note != self.note || !self.is_dirty() {
+                cmds.push(self.discard_snapshot(&self.session, note.as_deref()));
+                self.autosaved = None;
+            }
+        }
+        let is_changed = match &self.autosaved {
+            Some((_, content)) => *content != self.content,
+            None => true,
+        };
+        if self.is_dirty() && is_changed {
+            cmds.push(http::fetch_text(
+                "PUT",
+                &http::recovery_url(&self.session, self.note.as_deref()),
+                Some(self.content.clone()),
+                Msg::AutosaveDone,
+            ));
+            self.autosaved = Some((self.note.clone(), self.content.clone()));
+        }
+        Cmd::batch(cmds)
+    }
+
+    fn discard_snapshot(&self, session: &str, note: Option<&str>) -> Cmd<Self, Msg> {
+        http::fetch_text(
+            "DELETE",
+            &http::recovery_url(session, note),
+            None,
+            Msg::AutosaveDone,
+        )
+    }
+
+    fn load_snapshots(&self) -> Cmd<Self, Msg> {
+        http::fetch_text("GET", http::RECOVERY_URL, None, Msg::SnapshotsListed)
+    }
+
+    /// the content of the note on disk, without opening it
+    #[cfg(feature = "external-invoke")]
+    fn read_disk<F>(&self, note: String, on_read: F) -> Cmd<Self, Msg>
+    where
+        F: Fn(String, Result<DiskNote, String>) -> Msg + 'static,
+    {
+        invoke::invoke_command(Command::ReadFile { path: note.clone() }, move |result| {
+            let disk_note = match result {
+                Ok(CommandOutput::FileContent {
+                    content, version, ..
+                }) => Ok((content, Some(version))),
+                Ok(output) => Err(format!("unexpected output: {:?}", output)),
+                Err(e) => Err(e.message),
+            };
+            on_read(note.clone(), disk_note)
+        })
+    }
+
+    #[cfg(not(feature = "external-invoke"))]
+    fn read_disk<F>(&self, note: String, on_read: F) -> Cmd<Self, Msg>
+    where
+        F: Fn(String, Result<DiskNote, String>) -> Msg + 'static,
+    {
+        http::fetch_note("GET", &http::note_url(&note), None, None, move |response| {
+            let disk_note = match response {
+                Ok(NoteResponse::Note { content, version }) => Ok((content, version)),
+                Ok(NoteResponse::Modified) => Err("unexpected response".to_string()),
+                Err(e) => Err(e),
+            };
+            on_read(note.clone(), disk_note)
+        })
+    }
+
+    /// compare the note on disk with the version which was loaded
+    fn check_disk(&self) -> Cmd<Self, Msg> {
+        match &self.note {
+            Some(note) if self.conflict.is_none() => {
+                self.read_disk(note.to_string(), Msg::DiskNoteChecked)
+            }
+            _ => Cmd::none().no_render(),
+        }
+    }
+
+    /// The note was modified on disk when its version changed, or its content when the
+    /// version is not known. A clean buffer is reloaded, while the user is asked what to do
+    /// with the unsaved changes. A refused save is always a conflict.
+    fn disk_note_checked(&mut self, disk_note: DiskNote, on_save: bool) -> Cmd<Self, Msg> {
+        let (content, version) = disk_note;
+        let is_modified = match (&self.version, &version) {
+            (Some(loaded), Some(current)) => loaded.is_modified(current),
+            _ => content != self.saved_content,
+        };
+        if !is_modified && !on_save {
+            return Cmd::none().no_render();
+        }
+        if !self.is_dirty() {
+            log::info!("reloading {:?} which is modified on disk", self.note);
+            self.set_content(&content);
+            self.version = version;
+            return self.set_title();
+        }
+        let note = self.note.clone().unwrap_or_else(|| UNTITLED.to_string());
+        self.conflict = Some(
+            Conflict::new(
+                note,
+                self.saved_content.clone(),
+                self.content.clone(),
+                content,
+                version,
+                on_save,
+            )
+            .on_event(Msg::ConflictEvent),
+        );
+        Cmd::none()
+    }
+
+    /// The restored buffer is unsaved, so it is compared to the note on disk.
+    /// Its snapshot is replaced by a snapshot of this session.
+    fn restore_snapshot(
+        &mut self,
+        snapshot: Snapshot,
+        disk_note: Option<DiskNote>,
+    ) -> Cmd<Self, Msg> {
+        let (disk_content, version) = disk_note.unwrap_or_default();
+        let discard = self.discard_snapshot(&snapshot.session, snapshot.note.as_deref());
+        self.note = snapshot.note;
+        Cmd::batch(vec![
+            self.set_unsaved_content(&snapshot.content, disk_content, version),
+            self.autosave(),
+            discard,
+        ])
+    }
+
+    /// replace the content of the editor with content which differs from the note on disk
+    fn set_unsaved_content(
+        &mut self,
+        content: &str,
+        disk_content: String,
+        version: Option<NoteVersion>,
+    ) -> Cmd<Self, Msg> {
+        self.set_content(content);
+        self.saved_content = disk_content;
+        self.version = version;
+        self.is_dirty.set(self.is_dirty());
+        self.set_title()
+    }
+
+    #[cfg(feature = "external-invoke")]
+    fn load_recent(&self) -> Cmd<Self, Msg> {
+        invoke::invoke_command(Command::ListRecent, |result| {
+            Msg::RecentListed(recent_paths(result))
+        })
+    }
+
+    #[cfg(not(feature = "external-invoke"))]
+    fn load_recent(&self) -> Cmd<Self, Msg> {
+        http::fetch_text("GET", http::RECENT_URL, None, |response| {
+            Msg::RecentListed(
+                response.and_then(|json| serde_json::from_str(&json).map_err(|e| e.to_string())),
+            )
+        })
+    }
+
+    #[cfg(feature = "external-invoke")]
+    fn clear_recent(&self) -> Cmd<Self, Msg> {
+        invoke::invoke_command(Command::ClearRecent, |result| {
+            Msg::RecentListed(recent_paths(result))
+        })
+    }
+
+    #[cfg(not(feature = "external-invoke"))]
+    fn clear_recent(&self) -> Cmd<Self, Msg> {
+        http::fetch_text("DELETE", http::RECENT_URL, None, |response| {
+            Msg::RecentListed(response.map(|_| vec![]))
+        })
+    }
+
+    /// the license is checked by the desktop app before showing the about
+    #[cfg(feature = "external-invoke")]
+    fn show_about(&mut self) -> Cmd<Self, Msg> {
+        invoke::invoke_command(Command::LicenseStatus, Msg::LicenseStatus)
+    }
+
+    #[cfg(not(feature = "external-invoke"))]
+    fn show_about(&mut self) -> Cmd<Self, Msg> {
+        self.about = Some(About::new(false, None).on_event(Msg::AboutEvent));
+        Cmd::none()
+    }
+
+    #[cfg(feature = "external-invoke")]
+    fn activate_license(&self, license: String) -> Cmd<Self, Msg> {
+        invoke::invoke_command(Command::InstallLicense { license }, Msg::LicenseInstalled)
+    }
+
+    #[cfg(not(feature = "external-invoke"))]
+    fn activate_license(&self, _license: String) -> Cmd<Self, Msg> {
+        Cmd::none()
+    }
+
+    /// save with the native dialog of the desktop app, which is shown when there is no path
+    #[cfg(feature = "external-invoke")]
+    fn save(&self, save_as: bool) -> Cmd<Self, Msg> {
+        let (path, version) = if save_as {
+            (None, None)
+        } else {
+            (self.note.clone(), self.version.clone())
+        };
+        let content = self.content.clone();
+        invoke::invoke_command(
+            Command::SaveFile {
+                path,
+                content: content.clone(),
+                version,
+            },
+            move |result| Msg::FileSaved(content.clone(), result),
+        )
+    }
+
+    /// save to the workspace of the server, the name is asked when there is no note yet
+    #[cfg(not(feature = "external-invoke"))]
+    fn save(&self, save_as: bool) -> Cmd<Self, Msg> {
+        let content = self.content.clone();
+        match (&self.note, save_as) {
+            (Some(note), false) => {
+                let note = note.clone();
+                // the note is not written if it was modified on disk since it was loaded
+                http::fetch_note(
+                    "PUT",
+                    &http::note_url(&note),
+                    Some(content.clone()),
+                    self.version.clone(),
+                    move |response| Msg::NoteSaved(note.clone(), content.clone(), response),
+                )
+            }
+            _ => {
+                let default = self.note.as_deref().unwrap_or(UNTITLED_NOTE);
+                match dialog::prompt("Save as", default) {
+                    // creating a note fails if it already exists, so nothing is overwritten
+                    Some(note) => http::fetch_note(
+                        "POST",
+                        &http::note_url(&note),
+                        Some(content.clone()),
+                        None,
+                        move |response| Msg::NoteSaved(note.clone(), content.clone(), response),
+                    ),
+                    None => Cmd::none(),
+                }
+            }
+        }
+    }
+
+    /// whether the scroll of this pane was done by the app, rather than the user
+    fn is_synced_scroll(&mut self, pane: Pane, scroll_top: i32) -> bool {
+        match self.synced_scroll {
+            Some((synced_pane, synced_top)) if synced_pane == pane => {
+                self.synced_scroll = None;
+                synced_top == scroll_top
+            }
+            _ => false,
+        }
+    }
+
+    /// highlight the rendered block of the line where the cursor of the editor is
+    fn view_active_block(&self) -> Node<Msg> {
+        let line = self.editor.get_position().y as f64;
+        match self.rendered_markdown.source_map().block_at_line(line) {
+            Some((index, _)) => html::tags::style(
+                [],
+                [text(format!(
+                    "{} {{ background-color: {}; }}",
+                    scroll_sync::rendered_block_selector(index),
+                    ACTIVE_BLOCK_COLOR
+                ))],
+            ),
+            None => span([], []),
         }
     }
+
     fn set_separator_position(&mut self, client_x: i32, _client_y: i32) {
         if let Some(start) = self.separator.start {
             self.separator.offset_x = client_x - start.x;
@@ -76,9 +610,25 @@ impl App {
 
 impl Application<Msg> for App {
     fn init(&mut self) -> Cmd<Self, Msg> {
-        Window::add_event_listeners(vec![
-            on_mousemove(|me| Msg::WindowMousemove(me.client_x(), me.client_y())),
-            on_mouseup(|me| Msg::WindowMouseup(me.client_x(), me.client_y())),
+        self.session = recovery::new_session();
+        dialog::warn_before_unload(self.is_dirty.clone());
+        Cmd::batch(vec![
+            Window::add_event_listeners(vec![
+                on_mousemove(|me| Msg::WindowMousemove(me.client_x(), me.client_y())),
+                on_mouseup(|me| Msg::WindowMouseup(me.client_x(), me.client_y())),
+                on("focus", |_| Msg::WindowFocused),
+                on("blur", |_| Msg::WindowBlurred),
+            ]),
+            match self.initial_note.take() {
+                Some(note) => self.open_note(note),
+                None => Cmd::none(),
+            },
+            self.load_recent(),
+            self.load_snapshots(),
+            recovery::every(AUTOSAVE_INTERVAL, || Msg::AutosaveTick),
+            // there is no server to push events in the desktop app
+            #[cfg(not(feature = "external-invoke"))]
+            http::listen_note_events(http::EVENTS_URL, Msg::NoteEvent),
         ])
     }
 
@@ -86,8 +636,46 @@ impl Application<Msg> for App {
         match msg {
             Msg::EditorScrolled((scroll_top, scroll_left)) => {
                 self.editor_scroll = Point2::new(scroll_left, scroll_top);
+                if !self.is_synced_scroll(Pane::Editor, scroll_top) {
+                    self.synced_scroll = scroll_sync::sync_preview(
+                        self.rendered_markdown.source_map(),
+                        self.content.lines().count(),
+                        scroll_top,
+                    )
+                    .map(|top| (Pane::Preview, top));
+                }
                 Cmd::none()
             }
+            Msg::PreviewScrolled((scroll_top, _scroll_left)) => {
+                if !self.is_synced_scroll(Pane::Preview, scroll_top) {
+                    self.synced_scroll = scroll_sync::sync_editor(
+                        self.rendered_markdown.source_map(),
+                        self.content.lines().count(),
+                        scroll_top,
+                    )
+                    .map(|top| (Pane::Editor, top));
+                }
+                Cmd::none().no_render()
+            }
+            Msg::PreviewClicked(None) => Cmd::none().no_render(),
+            Msg::PreviewClicked(Some(client_y)) => {
+                let source_map = self.rendered_markdown.source_map();
+                let line = scroll_sync::rendered_block_at(client_y)
+                    .and_then(|index| source_map.line_in_block(index, 0.0));
+                match line {
+                    Some(line) => {
+                        let line = line as usize;
+                        self.editor.set_position(0, line);
+                        if let Some(top) =
+                            scroll_sync::reveal_line(line, self.content.lines().count())
+                        {
+                            self.synced_scroll = Some((Pane::Editor, top));
+                        }
+                        Cmd::none()
+                    }
+                    None => Cmd::none().no_render(),
+                }
+            }
             Msg::MenuMsg(mmsg) => {
                 log::trace!("menu msg: {:?}", mmsg);
                 let effects = self.menu.update(mmsg);
@@ -95,6 +683,16 @@ impl Application<Msg> for App {
             }
             Msg::MenuAction(menu_action) => match menu_action {
                 MenuAction::Open => self.update(Msg::OpenFileClicked),
+                MenuAction::OpenRecent(path) => {
+                    if self.is_dirty() && !dialog::confirm("Discard the unsaved changes?") {
+                        return Cmd::none().no_render();
+                    }
+                    self.open_note(path)
+                }
+                MenuAction::ClearRecent => self.clear_recent(),
+                MenuAction::About => self.show_about(),
+                MenuAction::Save => self.save(false),
+                MenuAction::SaveAs => self.save(true),
                 MenuAction::Undo => Cmd::from(self.editor.undo().localize(Msg::EditorMsg)),
                 MenuAction::Redo => Cmd::from(self.editor.redo().localize(Msg::EditorMsg)),
                 _ => Cmd::none(),
@@ -105,10 +703,8 @@ impl Application<Msg> for App {
                 Cmd::from(effects.localize(Msg::EditorMsg)).measure()
             }
             Msg::EditorContentChanged(content) => {
-                let effects = self
-                    .rendered_markdown
-                    .update(rendered_markdown::Msg::ContentChanged(content));
-                Cmd::none()
+                self.content = content;
+                self.update_dirty_state()
             }
             Msg::WindowMouseup(client_x, client_y) => {
                 self.menu.hide_menu();
@@ -147,15 +743,291 @@ impl Application<Msg> for App {
             }
             Msg::OpenFileClicked => {
                 log::trace!("open file is cliced..");
+                if self.is_dirty() && !dialog::confirm("Discard the unsaved changes?") {
+                    return Cmd::none().no_render();
+                }
                 #[cfg(feature = "external-invoke")]
-                invoke("open");
-                Cmd::none()
+                return invoke::invoke_command(Command::OpenFile, Msg::FileOpened);
+                // pick from the notes in the workspace of the server
+                #[cfg(not(feature = "external-invoke"))]
+                http::fetch_text("GET", http::NOTES_URL, None, Msg::NotesListed)
+            }
+            Msg::NotesListed(Ok(json)) => match serde_json::from_str::<Vec<NoteEntry>>(&json) {
+                Ok(notes) => {
+                    self.note_picker = Some(NotePicker::new(notes).on_pick(Msg::NotePicked));
+                    Cmd::none()
+                }
+                Err(e) => {
+                    log::error!("invalid listing of notes: {}", e);
+                    Cmd::none().no_render()
+                }
+            },
+            Msg::NotesListed(Err(e)) => {
+                log::error!("unable to list the notes: {}", e);
+                Cmd::none().no_render()
+            }
+            Msg::NotePickerMsg(pmsg) => match &mut self.note_picker {
+                Some(note_picker) => {
+                    let effects = note_picker.update(pmsg);
+                    Cmd::from(effects.localize(Msg::NotePickerMsg))
+                }
+                None => Cmd::none().no_render(),
+            },
+            Msg::NotePicked(picked) => {
+                self.note_picker = None;
+                match picked {
+                    Some(note) => self.open_note(note),
+                    None => Cmd::none(),
+                }
             }
             Msg::RenderedMarkdownMsg(rmsg) => {
                 log::trace!("---->Updating renderedmarkdown with: {:?}", rmsg);
                 let effects = self.rendered_markdown.update(rmsg);
                 Cmd::none()
             }
+            Msg::NoteLoaded(note, Ok(NoteResponse::Note { content, version })) => {
+                self.note = Some(note);
+                self.set_content(&content);
+                self.version = version;
+                Cmd::batch(vec![self.set_title(), self.load_recent()])
+            }
+            Msg::NoteLoaded(note, Ok(NoteResponse::Modified)) => {
+                log::error!("unexpected response when loading {}", note);
+                Cmd::none().no_render()
+            }
+            Msg::NoteLoaded(note, Err(e)) => {
+                log::error!("unable to load note {}: {}", note, e);
+                Cmd::none()
+            }
+            #[cfg(feature = "external-invoke")]
+            Msg::FileOpened(Ok(CommandOutput::Opened {
+                path,
+                content,
+                version,
+            })) => {
+                self.note = Some(path);
+                self.set_content(&content);
+                self.version = Some(version);
+                Cmd::batch(vec![self.set_title(), self.load_recent()])
+            }
+            Msg::FileOpened(Ok(output)) => {
+                log::trace!("open file: {:?}", output);
+                Cmd::none().no_render()
+            }
+            Msg::FileOpened(Err(e)) => {
+                log::error!("unable to open file: {}", e.message);
+                Cmd::none().no_render()
+            }
+            #[cfg(feature = "external-invoke")]
+            Msg::FileSaved(content, Ok(CommandOutput::Saved { path, version })) => {
+                self.content_saved(path, content, Some(version))
+            }
+            Msg::FileSaved(_, Ok(output)) => {
+                log::trace!("save file: {:?}", output);
+                Cmd::none().no_render()
+            }
+            Msg::FileSaved(_, Err(e)) if e.error == "precondition_failed" => match &self.note {
+                Some(note) => self.read_disk(note.to_string(), Msg::SaveConflict),
+                None => Cmd::none().no_render(),
+            },
+            Msg::FileSaved(_, Err(e)) => {
+                dialog::alert(&format!("Unable to save the file: {}", e.message));
+                Cmd::none().no_render()
+            }
+            Msg::NoteSaved(note, content, Ok(NoteResponse::Note { version, .. })) => {
+                self.content_saved(note, content, version)
+            }
+            Msg::NoteSaved(note, _, Ok(NoteResponse::Modified)) => {
+                self.read_disk(note, Msg::SaveConflict)
+            }
+            Msg::NoteSaved(note, _, Err(e)) => {
+                dialog::alert(&format!("Unable to save {}: {}", note, e));
+                Cmd::none().no_render()
+            }
+            Msg::CommandDone(result) => {
+                if let Err(e) = result {
+                    log::error!("command error: {}", e.message);
+                }
+                Cmd::none().no_render()
+            }
+            Msg::RecentListed(Ok(recent)) => {
+                self.menu.set_recent(recent);
+                Cmd::none()
+            }
+            Msg::RecentListed(Err(e)) => {
+                log::error!("unable to list the recent notes: {}", e);
+                Cmd::none().no_render()
+            }
+            Msg::AboutMsg(amsg) => match &mut self.about {
+                Some(about) => {
+                    let effects = about.update(amsg);
+                    Cmd::from(effects.localize(Msg::AboutMsg))
+                }
+                None => Cmd::none().no_render(),
+            },
+            Msg::AboutEvent(about::Event::Activate(license)) => self.activate_license(license),
+            Msg::AboutEvent(about::Event::Close) => {
+                self.about = None;
+                Cmd::none()
+            }
+            Msg::LicenseStatus(result) => {
+                let license = match result {
+                    Ok(CommandOutput::License { license }) => license,
+                    Ok(output) => {
+                        log::error!("unexpected license status: {:?}", output);
+                        None
+                    }
+                    Err(e) => {
+                        log::error!("unable to check the license: {}", e.message);
+                        None
+                    }
+                };
+                self.about = Some(About::new(true, license).on_event(Msg::AboutEvent));
+                Cmd::none()
+            }
+            Msg::LicenseInstalled(result) => {
+                if let Some(about) = &mut self.about {
+                    match result {
+                        Ok(CommandOutput::License { license }) => about.set_license(license),
+                        Ok(output) => about.set_error(format!("unexpected output: {:?}", output)),
+                        Err(e) => about.set_error(e.message),
+                    }
+                }
+                // the window title is no longer marked as unregistered
+                self.set_title()
+            }
+            Msg::SnapshotsListed(Ok(json)) => match serde_json::from_str::<Vec<Snapshot>>(&json) {
+                Ok(snapshots) if snapshots.is_empty() => Cmd::none().no_render(),
+                Ok(snapshots) => {
+                    let cmds = snapshots
+                        .iter()
+                        .filter_map(|snapshot| snapshot.note.clone())
+                        .map(|note| self.read_disk(note, Msg::DiskContentLoaded))
+                        .collect();
+                    self.recovery = Some(Recovery::new(snapshots).on_event(Msg::RecoveryEvent));
+                    Cmd::batch(cmds)
+                }
+                Err(e) => {
+                    log::error!("invalid listing of snapshots: {}", e);
+                    Cmd::none().no_render()
+                }
+            },
+            Msg::SnapshotsListed(Err(e)) => {
+                log::error!("unable to list the snapshots: {}", e);
+                Cmd::none().no_render()
+            }
+            Msg::DiskContentLoaded(note, Ok(disk_note)) => {
+                if let Some(recovery) = &mut self.recovery {
+                    recovery.set_disk_note(&note, disk_note);
+                }
+                Cmd::none()
+            }
+            Msg::DiskContentLoaded(note, Err(e)) => {
+                log::warn!(
+                    "the snapshot of {} is compared to an empty note: {}",
+                    note,
+                    e
+                );
+                Cmd::none().no_render()
+            }
+            Msg::RecoveryMsg(rmsg) => match &mut self.recovery {
+                Some(recovery) => {
+                    let effects = recovery.update(rmsg);
+                    Cmd::from(effects.localize(Msg::RecoveryMsg))
+                }
+                None => Cmd::none().no_render(),
+            },
+            Msg::RecoveryEvent(recovery::Event::Restore(snapshot, disk_note)) => {
+                if self.is_dirty() && !dialog::confirm("Discard the unsaved changes?") {
+                    return Cmd::none().no_render();
+                }
+                self.recovery = None;
+                self.restore_snapshot(snapshot, disk_note)
+            }
+            Msg::RecoveryEvent(recovery::Event::Discard(snapshot)) => {
+                if let Some(recovery) = &mut self.recovery {
+                    if recovery.remove(&snapshot) {
+                        self.recovery = None;
+                    }
+                }
+                self.discard_snapshot(&snapshot.session, snapshot.note.as_deref())
+            }
+            Msg::RecoveryEvent(recovery::Event::Close) => {
+                self.recovery = None;
+                Cmd::none()
+            }
+            Msg::AutosaveTick => self.autosave().no_render(),
+            Msg::AutosaveDone(result) => {
+                if let Err(e) = result {
+                    log::error!("unable to store the snapshot: {}", e);
+                }
+                Cmd::none().no_render()
+            }
+            Msg::WindowFocused => self.check_disk(),
+            #[cfg(feature = "external-invoke")]
+            Msg::WindowBlurred if self.is_dirty() => self.report_unsaved().no_render(),
+            Msg::TypingPaused(edits) if edits == self.edits => self.typing_paused(),
+            Msg::WindowBlurred | Msg::TypingPaused(_) => Cmd::none().no_render(),
+            Msg::DiskNoteChecked(note, Ok(disk_note)) if self.note.as_ref() == Some(&note) => {
+                self.disk_note_checked(disk_note, false)
+            }
+            Msg::DiskNoteChecked(_, Ok(_)) => Cmd::none().no_render(),
+            Msg::DiskNoteChecked(note, Err(e)) => {
+                log::warn!("unable to check {} on disk: {}", note, e);
+                Cmd::none().no_render()
+            }
+            Msg::SaveConflict(_, Ok(disk_note)) => self.disk_note_checked(disk_note, true),
+            Msg::SaveConflict(note, Err(e)) => {
+                dialog::alert(&format!(
+                    "{} was modified on disk, and it can not be read: {}",
+                    note, e
+                ));
+                Cmd::none().no_render()
+            }
+            Msg::ConflictMsg(cmsg) => match &mut self.conflict {
+                Some(conflict) => {
+                    let effects = conflict.update(cmsg);
+                    Cmd::from(effects.localize(Msg::ConflictMsg))
+                }
+                None => Cmd::none().no_render(),
+            },
+            Msg::ConflictEvent(conflict::Event::Reload { content, version }) => {
+                self.conflict = None;
+                self.set_content(&content);
+                self.version = version;
+                Cmd::batch(vec![self.set_title(), self.autosave()])
+            }
+            Msg::ConflictEvent(conflict::Event::KeepMine { version, on_save }) => {
+                self.conflict = None;
+                // the note on disk is overwritten by the next save
+                self.version = version;
+                if on_save {
+                    self.save(false)
+                } else {
+                    Cmd::none()
+                }
+            }
+            Msg::ConflictEvent(conflict::Event::UseMerged {
+                content,
+                disk_content,
+                version,
+            }) => {
+                self.conflict = None;
+                self.set_unsaved_content(&content, disk_content, version)
+            }
+            Msg::NoteEvent(note_event) => {
+                if self.note.as_deref() != Some(note_event.path()) {
+                    return Cmd::none();
+                }
+                match note_event {
+                    // this is also triggered by our own save, which has the same version
+                    NoteEvent::Changed { .. } => self.check_disk(),
+                    NoteEvent::Removed { path } => {
+                        log::warn!("{} has been removed from the disk", path);
+                        Cmd::none().no_render()
+                    }
+                }
+            }
         }
     }
 
@@ -173,6 +1045,26 @@ impl Application<Msg> for App {
             [class("container")],
             [
                 self.menu.view().map_msg(Msg::MenuMsg),
+                if let Some(note_picker) = &self.note_picker {
+                    note_picker.view().map_msg(Msg::NotePickerMsg)
+                } else {
+                    span([], [])
+                },
+                if let Some(about) = &self.about {
+                    about.view().map_msg(Msg::AboutMsg)
+                } else {
+                    span([], [])
+                },
+                if let Some(recovery) = &self.recovery {
+                    recovery.view().map_msg(Msg::RecoveryMsg)
+                } else {
+                    span([], [])
+                },
+                if let Some(conflict) = &self.conflict {
+                    conflict.view().map_msg(Msg::ConflictMsg)
+                } else {
+                    span([], [])
+                },
                 div(
                     [class("app")],
                     [
@@ -221,6 +1113,14 @@ impl Application<Msg> for App {
                         div(
                             [
                                 class("rendered_markdown"),
+                                on_scroll(Msg::PreviewScrolled),
+                                on_click(|me| {
+                                    if scroll_sync::is_interactive(&me) {
+                                        Msg::PreviewClicked(None)
+                                    } else {
+                                        Msg::PreviewClicked(Some(me.client_y()))
+                                    }
+                                }),
                                 style! {
                                     width: format!("calc({} - {})", percent(50), px(self.separator.offset_x)),
                                     cursor: if self.separator.is_dragging{
@@ -230,13 +1130,16 @@ impl Application<Msg> for App {
                                     },
                                 },
                             ],
-                            [div(
-                                [class("padded")],
-                                [self
-                                    .rendered_markdown
-                                    .view()
-                                    .map_msg(Msg::RenderedMarkdownMsg)],
-                            )],
+                            [
+                                div(
+                                    [class("padded")],
+                                    [self
+                                        .rendered_markdown
+                                        .view()
+                                        .map_msg(Msg::RenderedMarkdownMsg)],
+                                ),
+                                self.view_active_block(),
+                            ],
                         ),
                     ],
                 ),
@@ -317,7 +1220,43 @@ impl Application<Msg> for App {
             // fill in the `MSG` generic type from `View` trait.
             //<RenderedMarkdown as Component<Msg, ()>>::style(&self.rendered_markdown),
             self.rendered_markdown.style(),
+            note_picker::style(),
+            about::style(),
+            recovery::style(),
+            conflict::style(),
         ]
         .join("\n")
     }
 }
+
+/// the interval in milliseconds between the snapshots of the buffer
+const AUTOSAVE_INTERVAL: i32 = 5000;
+
+/// the time in milliseconds without edits after which the typing is paused
+const TYPING_PAUSE: i32 = 300;
+
+/// the background of the rendered block where the cursor of the editor is
+const ACTIVE_BLOCK_COLOR: &str = "#fdf6e3";
+
+/// the title when the content is not saved to a file yet
+const UNTITLED: &str = "Untitled";
+/// the default name when saving a new note to the workspace
+#[cfg(not(feature = "external-invoke"))]
+const UNTITLED_NOTE: &str = "untitled.md";
+
+/// the paths in the output of the recent commands
+#[cfg(feature = "external-invoke")]
+fn recent_paths(result: CommandResult) -> Result<Vec<String>, String> {
+    match result {
+        Ok(CommandOutput::Recent { paths }) => Ok(paths),
+        Ok(output) => Err(format!("unexpected output: {:?}", output)),
+        Err(e) => Err(e.message),
+    }
+}
+
+/// the last component of the path, which is used as the title
+fn file_name(path: &str) -> &str {
+    path.rsplit(|c| c == '/' || c == '\\')
+        .next()
+        .unwrap_or(path)
+}
@@ -0,0 +1,201 @@
+use crate::LicenseInfo;
+use sauron::html;
+use sauron::jss::jss;
+use sauron::prelude::*;
+use wasm_bindgen::JsValue;
+
+#[derive(Clone, Debug)]
+pub(crate) enum Msg {
+    EnterLicense,
+    LicenseChanged(String),
+    Activate,
+    Close,
+}
+
+/// The events emitted to the app
+#[derive(Clone, Debug)]
+pub(crate) enum Event {
+    /// verify and install this license
+    Activate(String),
+    Close,
+}
+
+/// A modal showing the app and its license, from where the license can be entered
+pub(crate) struct About<XMSG> {
+    /// the license is only used by the desktop app
+    can_register: bool,
+    license: Option<LicenseInfo>,
+    /// the license input is shown
+    is_entering: bool,
+    license_text: String,
+    /// the reason the entered license is refused
+    error: Option<String>,
+    listeners: Vec<Callback<Event, XMSG>>,
+}
+
+impl<XMSG> About<XMSG> {
+    pub(crate) fn new(can_register: bool, license: Option<LicenseInfo>) -> Self {
+        Self {
+            can_register,
+            license,
+            is_entering: false,
+            license_text: String::new(),
+            error: None,
+            listeners: vec![],
+        }
+    }
+
+    pub(crate) fn on_event<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Event) -> XMSG + 'static,
+    {
+        self.listeners.push(Callback::from(f));
+        self
+    }
+
+    /// the license is accepted
+    pub(crate) fn set_license(&mut self, license: Option<LicenseInfo>) {
+        self.license = license;
+        self.is_entering = false;
+        self.license_text.clear();
+        self.error = None;
+    }
+
+    pub(crate) fn set_error(&mut self, error: String) {
+        self.error = Some(error);
+    }
+
+    fn emit(&self, event: Event) -> Effects<Msg, XMSG> {
+        let xmsgs: Vec<XMSG> = self
+            .listeners
+            .iter()
+            .map(|listener| listener.emit(event.clone()))
+            .collect();
+        Effects::with_external(xmsgs)
+    }
+
+    fn view_license(&self) -> Node<Msg> {
+        if !self.can_register {
+            return p([], [text("The license is only needed by the desktop app.")]);
+        }
+        match &self.license {
+            Some(license) => div(
+                [class("license")],
+                [
+                    p([], [text!("Registered to {}", license.licensee)]),
+                    p(
+                        [],
+                        [match license.expires_at {
+                            Some(expires_at) => text!("Expires on {}", date_string(expires_at)),
+                            None => text("The license never expires"),
+                        }],
+                    ),
+                ],
+            ),
+            None if self.is_entering => div(
+                [class("license")],
+                [
+                    p([], [text("Paste the license below")]),
+                    textarea(
+                        [
+                            class("license_input"),
+                            value(self.license_text.clone()),
+                            on_input(|input| Msg::LicenseChanged(input.value)),
+                        ],
+                        [],
+                    ),
+                    if let Some(error) = &self.error {
+                        p([class("error")], [text(error)])
+                    } else {
+                        span([], [])
+                    },
+                    button([on_click(|_| Msg::Activate)], [text("Activate")]),
+                ],
+            ),
+            None => div(
+                [class("license")],
+                [
+                    p([], [text("Unregistered")]),
+                    button([on_click(|_| Msg::EnterLicense)], [text("Enter license")]),
+                ],
+            ),
+        }
+    }
+}
+
+impl<XMSG> Component<Msg, XMSG> for About<XMSG> {
+    fn update(&mut self, msg: Msg) -> Effects<Msg, XMSG> {
+        match msg {
+            Msg::EnterLicense => {
+                self.is_entering = true;
+                Effects::none()
+            }
+            Msg::LicenseChanged(license_text) => {
+                self.license_text = license_text;
+                self.error = None;
+                Effects::none()
+            }
+            Msg::Activate => self.emit(Event::Activate(self.license_text.clone())),
+            Msg::Close => self.emit(Event::Close),
+        }
+    }
+
+    fn view(&self) -> Node<Msg> {
+        div(
+            [class("about")],
+            [div(
+                [class("about_dialog")],
+                [
+                    html::h3([], [text(crate::APP_TITLE)]),
+                    p([], [text("Create and share notes")]),
+                    self.view_license(),
+                    button([on_click(|_| Msg::Close)], [text("Close")]),
+                ],
+            )],
+        )
+    }
+
+    fn style(&self) -> String {
+        style()
+    }
+}
+
+/// the style does not depend on the state, so the app can include it even when the modal
+/// is not shown
+pub(crate) fn style() -> String {
+    jss! {
+        ".about": {
+            position: "fixed",
+            top: 0,
+            left: 0,
+            width: percent(100),
+            height: percent(100),
+            background_color: "rgba(0, 0, 0, 0.3)",
+            z_index: 1000,
+        },
+
+        ".about_dialog": {
+            width: px(400),
+            margin: format!("{} auto", percent(10)),
+            padding: px(10),
+            background_color: "#fff",
+            border: format!("{} solid #ccc", px(1)),
+        },
+
+        ".about .license_input": {
+            width: percent(100),
+            height: px(120),
+            box_sizing: "border-box",
+        },
+
+        ".about .error": {
+            color: "#c00",
+        },
+    }
+}
+
+/// the local date of the unix time in seconds, eg: `Sun Oct 18 2026`
+fn date_string(unix_time: u64) -> String {
+    let millis = JsValue::from_f64(unix_time as f64 * 1000.0);
+    String::from(js_sys::Date::new(&millis).to_date_string())
+}
@@ -0,0 +1,259 @@
+use super::diff::{self, Merged};
+use crate::NoteVersion;
+use sauron::jss::jss;
+use sauron::prelude::*;
+
+#[derive(Clone, Debug)]
+pub(crate) enum Msg {
+    Reload,
+    KeepMine,
+    ShowMerge,
+    UseMerged,
+    Back,
+}
+
+/// The events emitted to the app
+#[derive(Clone, Debug)]
+pub(crate) enum Event {
+    /// discard the changes in the editor and load the note on disk
+    Reload {
+        content: String,
+        version: Option<NoteVersion>,
+    },
+    /// overwrite the note on disk with the content of the editor on the next save
+    KeepMine {
+        version: Option<NoteVersion>,
+        /// the conflict was found when saving, so the save is done again
+        on_save: bool,
+    },
+    /// replace the content of the editor with the merged content,
+    /// which is compared to the note on disk
+    UseMerged {
+        content: String,
+        disk_content: String,
+        version: Option<NoteVersion>,
+    },
+}
+
+/// A modal shown when the note was modified by another program while it has unsaved changes
+pub(crate) struct Conflict<XMSG> {
+    note: String,
+    /// the content when the note was loaded or saved, which both sides have changed
+    base: String,
+    /// the content of the editor
+    mine: String,
+    /// the content of the note on disk and its version
+    theirs: String,
+    version: Option<NoteVersion>,
+    on_save: bool,
+    /// the three-way merge, shown when the user asked for it
+    merged: Option<Merged>,
+    listeners: Vec<Callback<Event, XMSG>>,
+}
+
+impl<XMSG> Conflict<XMSG> {
+    pub(crate) fn new(
+        note: String,
+        base: String,
+        mine: String,
+        theirs: String,
+        version: Option<NoteVersion>,
+        on_save: bool,
+    ) -> Self {
+        Self {
+            note,
+            base,
+            mine,
+            theirs,
+            version,
+            on_save,
+            merged: None,
+            listeners: vec![],
+        }
+    }
+
+    pub(crate) fn on_event<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Event) -> XMSG + 'static,
+    {
+        self.listeners.push(Callback::from(f));
+        self
+    }
+
+    fn emit(&self, event: Event) -> Effects<Msg, XMSG> {
+        let xmsgs: Vec<XMSG> = self
+            .listeners
+            .iter()
+            .map(|listener| listener.emit(event.clone()))
+            .collect();
+        Effects::with_external(xmsgs)
+    }
+
+    fn view_choices(&self) -> Node<Msg> {
+        div(
+            [],
+            [
+                p(
+                    [],
+                    [text!(
+                        "{} was modified on disk while it has unsaved changes.",
+                        self.note
+                    )],
+                ),
+                div(
+                    [class("conflict_actions")],
+                    [
+                        button([on_click(|_| Msg::Reload)], [text("Reload")]),
+                        button([on_click(|_| Msg::KeepMine)], [text("Keep mine")]),
+                        button([on_click(|_| Msg::ShowMerge)], [text("Merge")]),
+                    ],
+                ),
+            ],
+        )
+    }
+
+    fn view_merged(&self, merged: &Merged) -> Node<Msg> {
+        let mut in_conflict = false;
+        div(
+            [],
+            [
+                p(
+                    [],
+                    [if merged.conflicts == 0 {
+                        text("The changes were merged without conflicts.")
+                    } else {
+                        text!(
+                            "{} conflicting changes are marked, they can be edited after the merge.",
+                            merged.conflicts
+                        )
+                    }],
+                ),
+                pre(
+                    [class("merged")],
+                    merged.content.lines().map(|line| {
+                        let is_marker = line == diff::CONFLICT_START
+                            || line == diff::CONFLICT_SEPARATOR
+                            || line == diff::CONFLICT_END;
+                        if line == diff::CONFLICT_START {
+                            in_conflict = true;
+                        }
+                        let class_name = if is_marker {
+                            "marker"
+                        } else if in_conflict {
+                            "conflicting"
+                        } else {
+                            ""
+                        };
+                        if line == diff::CONFLICT_END {
+                            in_conflict = false;
+                        }
+                        div([class(class_name)], [text(line)])
+                    }),
+                ),
+                div(
+                    [class("conflict_actions")],
+                    [
+                        button([on_click(|_| Msg::Back)], [text("Back")]),
+                        button([on_click(|_| Msg::UseMerged)], [text("Use merged")]),
+                    ],
+                ),
+            ],
+        )
+    }
+}
+
+impl<XMSG> Component<Msg, XMSG> for Conflict<XMSG> {
+    fn update(&mut self, msg: Msg) -> Effects<Msg, XMSG> {
+        match msg {
+            Msg::Reload => self.emit(Event::Reload {
+                content: self.theirs.clone(),
+                version: self.version.clone(),
+            }),
+            Msg::KeepMine => self.emit(Event::KeepMine {
+                version: self.version.clone(),
+                on_save: self.on_save,
+            }),
+            Msg::ShowMerge => {
+                self.merged = Some(diff::merge(&self.base, &self.mine, &self.theirs));
+                Effects::none()
+            }
+            Msg::UseMerged => match &self.merged {
+                Some(merged) => self.emit(Event::UseMerged {
+                    content: merged.content.clone(),
+                    disk_content: self.theirs.clone(),
+                    version: self.version.clone(),
+                }),
+                None => Effects::none(),
+            },
+            Msg::Back => {
+                self.merged = None;
+                Effects::none()
+            }
+        }
+    }
+
+    fn view(&self) -> Node<Msg> {
+        div(
+            [class("conflict")],
+            [div(
+                [class("conflict_dialog")],
+                [match &self.merged {
+                    Some(merged) => self.view_merged(merged),
+                    None => self.view_choices(),
+                }],
+            )],
+        )
+    }
+
+    fn style(&self) -> String {
+        style()
+    }
+}
+
+/// the style does not depend on the state, so the app can include it even when the modal
+/// is not shown
+pub(crate) fn style() -> String {
+    jss! {
+        ".conflict": {
+            position: "fixed",
+            top: 0,
+            left: 0,
+            width: percent(100),
+            height: percent(100),
+            background_color: "rgba(0, 0, 0, 0.3)",
+            z_index: 1000,
+        },
+
+        ".conflict_dialog": {
+            width: px(700),
+            max_height: percent(80),
+            margin: format!("{} auto", percent(5)),
+            padding: px(10),
+            display: "flex",
+            flex_direction: "column",
+            background_color: "#fff",
+            border: format!("{} solid #ccc", px(1)),
+        },
+
+        ".conflict .merged": {
+            max_height: px(400),
+            overflow: "auto",
+            padding: px(5),
+            border: format!("{} solid #ddd", px(1)),
+        },
+
+        ".conflict .merged .marker": {
+            color: "#888",
+        },
+
+        ".conflict .merged .conflicting": {
+            background_color: "#fff5b1",
+        },
+
+        ".conflict_actions": {
+            display: "flex",
+            justify_content: "flex-end",
+            padding_top: px(5),
+        },
+    }
+}
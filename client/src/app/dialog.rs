@@ -0,0 +1,53 @@
+//! The browser dialogs used when the native dialogs of the desktop app are not available
+use std::cell::Cell;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsCast;
+use web_sys::BeforeUnloadEvent;
+
+/// ask the user to confirm, this is false when there is no window
+pub(crate) fn confirm(message: &str) -> bool {
+    web_sys::window()
+        .and_then(|window| window.confirm_with_message(message).ok())
+        .unwrap_or(false)
+}
+
+/// ask the user for a text, this is None when the user cancelled
+#[cfg(not(feature = "external-invoke"))]
+pub(crate) fn prompt(message: &str, default: &str) -> Option<String> {
+    web_sys::window()?
+        .prompt_with_message_and_default(message, default)
+        .ok()
+        .flatten()
+        .filter(|answer| !answer.trim().is_empty())
+}
+
+pub(crate) fn alert(message: &str) {
+    if let Some(window) = web_sys::window() {
+        if window.alert_with_message(message).is_err() {
+            log::error!("unable to show alert: {}", message);
+        }
+    }
+}
+
+/// Let the browser warn the user before leaving the page while there are unsaved changes.
+/// The flag is shared with the app, which sets it whenever the dirty state changes.
+/// This does not fire when the window of the desktop app is closed, its unsaved changes are
+/// reported with the `Unsaved` command instead.
+pub(crate) fn warn_before_unload(is_dirty: Rc<Cell<bool>>) {
+    let window = match web_sys::window() {
+        Some(window) => window,
+        None => return,
+    };
+    let on_beforeunload: Closure<dyn FnMut(BeforeUnloadEvent)> =
+        Closure::wrap(Box::new(move |event: BeforeUnloadEvent| {
+            if is_dirty.get() {
+                event.prevent_default();
+                // needed by the older browsers to show the dialog
+                event.set_return_value("There are unsaved changes");
+            }
+        }));
+    window.set_onbeforeunload(Some(on_beforeunload.as_ref().unchecked_ref()));
+    // the listener is kept for the whole lifetime of the page
+    on_beforeunload.forget();
+}
@@ -0,0 +1,158 @@
+//! Line based diff and three-way merge, used to show the changes of a recovered buffer and to
+//! merge the changes made to a note on disk while it is edited
+
+/// the number of lines compared with each other, above this the changed lines are not matched
+const MAX_COMPARISONS: usize = 4_000_000;
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Line<'a> {
+    Same(&'a str),
+    Added(&'a str),
+    Removed(&'a str),
+}
+
+/// The result of a three-way merge.
+/// The conflicting lines are enclosed in `<<<<<<<`, `=======` and `>>>>>>>` markers.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Merged {
+    pub(crate) content: String,
+    pub(crate) conflicts: usize,
+}
+
+pub(crate) const CONFLICT_START: &str = "<<<<<<< mine";
+pub(crate) const CONFLICT_SEPARATOR: &str = "=======";
+pub(crate) const CONFLICT_END: &str = ">>>>>>> disk";
+
+/// The changes from `old` to `new`, from the longest common subsequence of their lines.
+pub(crate) fn diff_lines<'a>(old: &'a str, new: &'a str) -> Vec<Line<'a>> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+    let mut lines = vec![];
+    let (mut i, mut j) = (0, 0);
+    for (old_index, new_index) in common_lines(&old, &new) {
+        lines.extend(old[i..old_index].iter().map(|line| Line::Removed(line)));
+        lines.extend(new[j..new_index].iter().map(|line| Line::Added(line)));
+        lines.push(Line::Same(old[old_index]));
+        i = old_index + 1;
+        j = new_index + 1;
+    }
+    lines.extend(old[i..].iter().map(|line| Line::Removed(line)));
+    lines.extend(new[j..].iter().map(|line| Line::Added(line)));
+    lines
+}
+
+/// Merge the changes made from `base` in both `mine` and `theirs`.
+/// The regions changed on only one side are taken from that side, while the regions changed
+/// differently on both sides are conflicts which contain both versions.
+pub(crate) fn merge(base: &str, mine: &str, theirs: &str) -> Merged {
+    let base_lines: Vec<&str> = base.lines().collect();
+    let mine_lines: Vec<&str> = mine.lines().collect();
+    let theirs_lines: Vec<&str> = theirs.lines().collect();
+    let in_mine = matching(base_lines.len(), common_lines(&base_lines, &mine_lines));
+    let in_theirs = matching(base_lines.len(), common_lines(&base_lines, &theirs_lines));
+
+    let mut merged: Vec<&str> = vec![];
+    let mut conflicts = 0;
+    let (mut b, mut m, mut t) = (0, 0, 0);
+    loop {
+        // the next base line which is kept on both sides
+        let sync = (b..base_lines.len())
+            .find_map(|k| Some((k, in_mine[k]?, in_theirs[k]?)))
+            .unwrap_or((base_lines.len(), mine_lines.len(), theirs_lines.len()));
+        let (base_chunk, mine_chunk, theirs_chunk) = (
+            &base_lines[b..sync.0],
+            &mine_lines[m..sync.1],
+            &theirs_lines[t..sync.2],
+        );
+        if mine_chunk == base_chunk || mine_chunk == theirs_chunk {
+            merged.extend(theirs_chunk);
+        } else if theirs_chunk == base_chunk {
+            merged.extend(mine_chunk);
+        } else {
+            conflicts += 1;
+            merged.push(CONFLICT_START);
+            merged.extend(mine_chunk);
+            merged.push(CONFLICT_SEPARATOR);
+            merged.extend(theirs_chunk);
+            merged.push(CONFLICT_END);
+        }
+        if sync.0 == base_lines.len() {
+            break;
+        }
+        merged.push(base_lines[sync.0]);
+        b = sync.0 + 1;
+        m = sync.1 + 1;
+        t = sync.2 + 1;
+    }
+    let mut content = merged.join("\n");
+    if mine.ends_with('\n') || theirs.ends_with('\n') {
+        content.push('\n');
+    }
+    Merged { content, conflicts }
+}
+
+/// the index in the other text of each line of the base, None if it is not kept
+fn matching(len: usize, pairs: Vec<(usize, usize)>) -> Vec<Option<usize>> {
+    let mut matches = vec![None; len];
+    for (base_index, index) in pairs {
+        matches[base_index] = Some(index);
+    }
+    matches
+}
+
+/// The indexes of the lines in the longest common subsequence of `old` and `new`.
+/// The common lines at the start and the end are skipped before comparing the rest.
+fn common_lines(old: &[&str], new: &[&str]) -> Vec<(usize, usize)> {
+    let prefix = old
+        .iter()
+        .zip(new.iter())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(old, new)| old == new)
+        .count();
+    let old_changed = &old[prefix..old.len() - suffix];
+    let new_changed = &new[prefix..new.len() - suffix];
+
+    let mut pairs: Vec<(usize, usize)> = (0..prefix).map(|i| (i, i)).collect();
+    if old_changed.len() * new_changed.len() <= MAX_COMPARISONS {
+        pairs.extend(
+            common_subsequence(old_changed, new_changed)
+                .into_iter()
+                .map(|(i, j)| (prefix + i, prefix + j)),
+        );
+    }
+    pairs.extend((0..suffix).map(|i| (old.len() - suffix + i, new.len() - suffix + i)));
+    pairs
+}
+
+fn common_subsequence(old: &[&str], new: &[&str]) -> Vec<(usize, usize)> {
+    // lengths[i][j] is the length of the common subsequence of old[i..] and new[j..]
+    let mut lengths = vec![vec![0usize; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lengths[i][j] = if old[i] == new[j] {
+                lengths[i + 1][j + 1] + 1
+            } else {
+                lengths[i + 1][j].max(lengths[i][j + 1])
+            };
+        }
+    }
+    let mut pairs = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() && j < new.len() {
+        if old[i] == new[j] {
+            pairs.push((i, j));
+            i += 1;
+            j += 1;
+        } else if lengths[i + 1][j] >= lengths[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    pairs
+}
@@ -0,0 +1,234 @@
+use crate::{NoteEvent, NoteVersion, MODIFIED_HEADER};
+use sauron::prelude::*;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+use wasm_bindgen_futures::{spawn_local, JsFuture};
+use web_sys::{EventSource, Headers, MessageEvent, Request, RequestInit, Response};
+
+/// the listing of the notes in the workspace
+pub(crate) const NOTES_URL: &str = "/api/notes";
+
+/// the notes recently opened or saved in the workspace
+pub(crate) const RECENT_URL: &str = "/api/recent";
+
+/// the snapshots of the unsaved buffers
+pub(crate) const RECOVERY_URL: &str = "/api/recovery";
+
+/// the status of a save which is refused because the note was modified on disk
+const PRECONDITION_FAILED: u16 = 412;
+
+/// the endpoint where the server pushes the modified notes
+pub(crate) const EVENTS_URL: &str = "/api/events";
+
+/// Make a request to the server and dispatch the msg created from the text response.
+/// A response with a non-success status is passed as an `Err` containing the status
+/// and the body of the response.
+pub(crate) fn fetch_text<APP, MSG, F>(
+    method: &str,
+    url: &str,
+    body: Option<String>,
+    on_response: F,
+) -> Cmd<APP, MSG>
+where
+    APP: Application<MSG> + 'static,
+    MSG: 'static,
+    F: Fn(Result<String, String>) -> MSG + 'static,
+{
+    let method = method.to_string();
+    let url = url.to_string();
+    let on_response = Rc::new(on_response);
+    Cmd::new(move |program| {
+        let method = method.clone();
+        let url = url.clone();
+        let body = body.clone();
+        let on_response = on_response.clone();
+        spawn_local(async move {
+            let result = request_text(&method, &url, body).await;
+            program.dispatch(on_response(result));
+        });
+    })
+}
+
+async fn request_text(method: &str, url: &str, body: Option<String>) -> Result<String, String> {
+    let response = request(method, url, body, &[]).await?;
+    let text = response_text(&response).await?;
+    if response.ok() {
+        Ok(text)
+    } else {
+        Err(format!("{}: {}", response.status(), text))
+    }
+}
+
+/// The response of the notes api
+pub(crate) enum NoteResponse {
+    /// the content of the note, which is empty for a save, and its version on disk
+    Note {
+        content: String,
+        version: Option<NoteVersion>,
+    },
+    /// the note was not saved, since it was modified on disk after the expected version
+    Modified,
+}
+
+/// Make a request to the notes api. When the version is given, the note is only written if it
+/// is still this version on disk.
+pub(crate) fn fetch_note<APP, MSG, F>(
+    method: &str,
+    url: &str,
+    body: Option<String>,
+    version: Option<NoteVersion>,
+    on_response: F,
+) -> Cmd<APP, MSG>
+where
+    APP: Application<MSG> + 'static,
+    MSG: 'static,
+    F: Fn(Result<NoteResponse, String>) -> MSG + 'static,
+{
+    let method = method.to_string();
+    let url = url.to_string();
+    let on_response = Rc::new(on_response);
+    Cmd::new(move |program| {
+        let method = method.clone();
+        let url = url.clone();
+        let body = body.clone();
+        let version = version.clone();
+        let on_response = on_response.clone();
+        spawn_local(async move {
+            let result = request_note(&method, &url, body, version).await;
+            program.dispatch(on_response(result));
+        });
+    })
+}
+
+async fn request_note(
+    method: &str,
+    url: &str,
+    body: Option<String>,
+    version: Option<NoteVersion>,
+) -> Result<NoteResponse, String> {
+    let headers = match version {
+        Some(version) => vec![
+            ("If-Match", version.etag()),
+            (MODIFIED_HEADER, version.modified.to_string()),
+        ],
+        None => vec![],
+    };
+    let response = request(method, url, body, &headers).await?;
+    if response.status() == PRECONDITION_FAILED {
+        return Ok(NoteResponse::Modified);
+    }
+    let content = response_text(&response).await?;
+    if !response.ok() {
+        return Err(format!("{}: {}", response.status(), content));
+    }
+    let header = |name: &str| response.headers().get(name).ok().flatten();
+    let version = match (header("ETag"), header(MODIFIED_HEADER)) {
+        (Some(etag), Some(modified)) => NoteVersion::from_headers(&etag, &modified),
+        _ => None,
+    };
+    Ok(NoteResponse::Note { content, version })
+}
+
+async fn request(
+    method: &str,
+    url: &str,
+    body: Option<String>,
+    headers: &[(&str, String)],
+) -> Result<Response, String> {
+    let mut opts = RequestInit::new();
+    opts.method(method);
+    if let Some(body) = body {
+        opts.body(Some(&JsValue::from_str(&body)));
+    }
+    let request_headers = Headers::new().map_err(js_error)?;
+    for (name, value) in headers {
+        request_headers.set(name, value).map_err(js_error)?;
+    }
+    opts.headers(&request_headers);
+    let request = Request::new_with_str_and_init(url, &opts).map_err(js_error)?;
+    let window = web_sys::window().ok_or_else(|| "no window".to_string())?;
+    JsFuture::from(window.fetch_with_request(&request))
+        .await
+        .map_err(js_error)?
+        .dyn_into()
+        .map_err(js_error)
+}
+
+async fn response_text(response: &Response) -> Result<String, String> {
+    Ok(JsFuture::from(response.text().map_err(js_error)?)
+        .await
+        .map_err(js_error)?
+        .as_string()
+        .unwrap_or_default())
+}
+
+/// Listen to the notes modified on disk which are pushed by the server as Server-Sent Events.
+pub(crate) fn listen_note_events<APP, MSG, F>(url: &str, on_event: F) -> Cmd<APP, MSG>
+where
+    APP: Application<MSG> + 'static,
+    MSG: 'static,
+    F: Fn(NoteEvent) -> MSG + 'static,
+{
+    let url = url.to_string();
+    let on_event = Rc::new(on_event);
+    Cmd::new(move |program| {
+        let event_source = match EventSource::new(&url) {
+            Ok(event_source) => event_source,
+            Err(e) => {
+                log::error!("unable to listen to {}: {}", url, js_error(e));
+                return;
+            }
+        };
+        let on_event = on_event.clone();
+        let on_message: Closure<dyn FnMut(MessageEvent)> =
+            Closure::wrap(Box::new(move |event: MessageEvent| {
+                let data = event.data().as_string().unwrap_or_default();
+                match serde_json::from_str(&data) {
+                    Ok(note_event) => program.dispatch(on_event(note_event)),
+                    Err(e) => log::error!("invalid note event {}: {}", data, e),
+                }
+            }));
+        event_source.set_onmessage(Some(on_message.as_ref().unchecked_ref()));
+        // the listener is kept for the whole lifetime of the page
+        on_message.forget();
+    })
+}
+
+/// the url of the note in the server notes api
+pub(crate) fn note_url(path: &str) -> String {
+    format!("/api/notes/{}", encode_path(path.trim_start_matches('/')))
+}
+
+/// the url of the note which is opened in the app, rather than only read, so the server adds
+/// it to the recent notes
+pub(crate) fn open_note_url(path: &str) -> String {
+    format!("{}?open", note_url(path))
+}
+
+/// the url of the snapshot of the note taken in the session, the notes of the desktop app are
+/// absolute paths so the leading `/` is kept
+pub(crate) fn recovery_url(session: &str, note: Option<&str>) -> String {
+    match note {
+        Some(note) => format!(
+            "{}/{}/{}",
+            RECOVERY_URL,
+            encode_path(session),
+            encode_path(note)
+        ),
+        None => format!("{}/{}", RECOVERY_URL, encode_path(session)),
+    }
+}
+
+/// Encode each segment of the path, so the names containing `#`, `?`, `%` or spaces are not
+/// mistaken for the other parts of the url. The separators and the leading `/` are kept.
+fn encode_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| String::from(js_sys::encode_uri_component(segment)))
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn js_error(e: JsValue) -> String {
+    e.as_string().unwrap_or_else(|| format!("{:?}", e))
+}
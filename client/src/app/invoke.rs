@@ -0,0 +1,38 @@
+use crate::command::{Command, CommandError, CommandResult};
+use sauron::prelude::*;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::JsValue;
+
+#[wasm_bindgen]
+extern "C" {
+    /// defined in `invoke.js`
+    #[wasm_bindgen(js_name = invoke_command)]
+    fn invoke_command_js(command: &str, callback: &JsValue);
+}
+
+/// Send the command to the desktop app and dispatch the msg created from its response.
+pub(crate) fn invoke_command<APP, MSG, F>(command: Command, on_response: F) -> Cmd<APP, MSG>
+where
+    APP: Application<MSG> + 'static,
+    MSG: 'static,
+    F: Fn(CommandResult) -> MSG + 'static,
+{
+    let on_response = Rc::new(on_response);
+    Cmd::new(move |program| {
+        let json = match serde_json::to_string(&command) {
+            Ok(json) => json,
+            Err(e) => {
+                program.dispatch(on_response(Err(CommandError::new("bad_request", e))));
+                return;
+            }
+        };
+        let on_response = on_response.clone();
+        let callback = Closure::once_into_js(move |response: String| {
+            let result = serde_json::from_str(&response)
+                .unwrap_or_else(|e| Err(CommandError::new("internal", e)));
+            program.dispatch(on_response(result));
+        });
+        invoke_command_js(&json, &callback);
+    })
+}
@@ -13,7 +13,9 @@ pub(crate) enum MenuList {
 #[derive(Clone, Debug, PartialEq)]
 pub(crate) enum MenuAction {
     Open,
-    OpenRecent,
+    /// open this note from the recent list
+    OpenRecent(String),
+    ClearRecent,
     Save,
     SaveAs,
     Edit,
@@ -34,6 +36,8 @@ pub(crate) enum Msg {
 
 pub(crate) struct Menu<XMSG> {
     active_menu_list: Option<MenuList>,
+    /// the notes listed in the "Open recent.." submenu, the most recent first
+    recent: Vec<String>,
     listeners: Vec<Callback<MenuAction, XMSG>>,
 }
 
@@ -41,6 +45,7 @@ impl<XMSG> Default for Menu<XMSG> {
     fn default() -> Self {
         Self {
             active_menu_list: None,
+            recent: vec![],
             listeners: vec![],
         }
     }
@@ -96,8 +101,8 @@ impl<XMSG> Component<Msg, XMSG> for Menu<XMSG> {
                                 [text("Open")],
                             ),
                             li(
-                                [on_click(|_| Msg::SelectAction(MenuAction::OpenRecent))],
-                                [text("Open recent..")],
+                                [class("submenu")],
+                                [text("Open recent.."), self.view_recent()],
                             ),
                             li(
                                 [on_click(|_| Msg::SelectAction(MenuAction::Save))],
@@ -234,6 +239,34 @@ impl<XMSG> Component<Msg, XMSG> for Menu<XMSG> {
             ".menu details li:hover": {
                 background_color: "#ddd",
             },
+
+            ".menu details li.submenu": {
+                position: "relative",
+            },
+
+            ".menu details .submenu_items": {
+                display: "none",
+                position: "absolute",
+                top: px(-1),
+                left: percent(100),
+                min_width: px(200),
+                margin: 0,
+                padding: 0,
+                background_color: "#eee",
+                border: format!("{} solid #ccc", px(1)),
+            },
+
+            ".menu details li.submenu:hover .submenu_items": {
+                display: "block",
+            },
+
+            ".menu details .submenu_items li": {
+                white_space: "nowrap",
+            },
+
+            ".menu details .submenu_items li.disabled": {
+                color: "#888",
+            },
         }
     }
 }
@@ -251,6 +284,35 @@ impl<XMSG> Menu<XMSG> {
         self
     }
 
+    pub(crate) fn set_recent(&mut self, recent: Vec<String>) {
+        self.recent = recent;
+    }
+
+    fn view_recent(&self) -> Node<Msg> {
+        let mut items: Vec<Node<Msg>> = self
+            .recent
+            .iter()
+            .map(|path| {
+                let recent = path.clone();
+                li(
+                    [on_click(move |_| {
+                        Msg::SelectAction(MenuAction::OpenRecent(recent.clone()))
+                    })],
+                    [text(path)],
+                )
+            })
+            .collect();
+        if items.is_empty() {
+            items.push(li([class("disabled")], [text("No recent notes")]));
+        } else {
+            items.push(li(
+                [on_click(|_| Msg::SelectAction(MenuAction::ClearRecent))],
+                [text("Clear recent")],
+            ));
+        }
+        ul([class("submenu_items")], items)
+    }
+
     /// call this when the click is outside of the menu
     pub(crate) fn hide_menu(&mut self) {
         self.active_menu_list = None;
@@ -0,0 +1,161 @@
+use crate::NoteEntry;
+use sauron::jss::jss;
+use sauron::prelude::*;
+
+#[derive(Clone, Debug)]
+pub(crate) enum Msg {
+    FilterChanged(String),
+    Pick(String),
+    Close,
+}
+
+/// A modal listing the notes in the workspace, used to open a note in the server mode
+pub(crate) struct NotePicker<XMSG> {
+    notes: Vec<NoteEntry>,
+    /// only the notes which contains this text in their path are listed
+    filter: String,
+    /// called with the path of the picked note, or `None` when the picker is closed
+    listeners: Vec<Callback<Option<String>, XMSG>>,
+}
+
+impl<XMSG> NotePicker<XMSG> {
+    pub(crate) fn new(notes: Vec<NoteEntry>) -> Self {
+        Self {
+            notes,
+            filter: String::new(),
+            listeners: vec![],
+        }
+    }
+
+    pub(crate) fn on_pick<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Option<String>) -> XMSG + 'static,
+    {
+        self.listeners.push(Callback::from(f));
+        self
+    }
+
+    fn emit(&self, picked: Option<String>) -> Effects<Msg, XMSG> {
+        let xmsgs: Vec<XMSG> = self
+            .listeners
+            .iter()
+            .map(|listener| listener.emit(picked.clone()))
+            .collect();
+        Effects::with_external(xmsgs)
+    }
+}
+
+impl<XMSG> Component<Msg, XMSG> for NotePicker<XMSG> {
+    fn update(&mut self, msg: Msg) -> Effects<Msg, XMSG> {
+        match msg {
+            Msg::FilterChanged(filter) => {
+                self.filter = filter;
+                Effects::none()
+            }
+            Msg::Pick(path) => self.emit(Some(path)),
+            Msg::Close => self.emit(None),
+        }
+    }
+
+    fn view(&self) -> Node<Msg> {
+        let filter = self.filter.to_lowercase();
+        div(
+            [class("note_picker")],
+            [div(
+                [class("note_picker_dialog")],
+                [
+                    div(
+                        [class("note_picker_header")],
+                        [
+                            text("Open note"),
+                            button([on_click(|_| Msg::Close)], [text("Close")]),
+                        ],
+                    ),
+                    input(
+                        [
+                            r#type("text"),
+                            placeholder("Filter.."),
+                            value(self.filter.clone()),
+                            on_input(|input| Msg::FilterChanged(input.value)),
+                        ],
+                        [],
+                    ),
+                    ul(
+                        [],
+                        self.notes
+                            .iter()
+                            .filter(|note| note.path.to_lowercase().contains(&filter))
+                            .map(|note| {
+                                let path = note.path.clone();
+                                li(
+                                    [on_click(move |_| Msg::Pick(path.clone()))],
+                                    [
+                                        text(&note.path),
+                                        span([class("size")], [text!("{} bytes", note.size)]),
+                                    ],
+                                )
+                            }),
+                    ),
+                ],
+            )],
+        )
+    }
+
+    fn style(&self) -> String {
+        style()
+    }
+}
+
+/// the style does not depend on the state, so the app can include it even when the picker
+/// is not shown
+pub(crate) fn style() -> String {
+    jss! {
+        ".note_picker": {
+            position: "fixed",
+            top: 0,
+            left: 0,
+            width: percent(100),
+            height: percent(100),
+            background_color: "rgba(0, 0, 0, 0.3)",
+            z_index: 1000,
+        },
+
+        ".note_picker_dialog": {
+            width: px(500),
+            max_height: percent(70),
+            margin: format!("{} auto", percent(10)),
+            padding: px(10),
+            display: "flex",
+            flex_direction: "column",
+            background_color: "#fff",
+            border: format!("{} solid #ccc", px(1)),
+        },
+
+        ".note_picker_header": {
+            display: "flex",
+            justify_content: "space-between",
+            padding_bottom: px(5),
+        },
+
+        ".note_picker ul": {
+            overflow: "auto",
+            padding: 0,
+        },
+
+        ".note_picker li": {
+            list_style: "none",
+            padding: px([5, 5]),
+            border_bottom: format!("{} solid #ddd", px(1)),
+            cursor: "default",
+        },
+
+        ".note_picker li:hover": {
+            background_color: "#ddd",
+        },
+
+        ".note_picker li .size": {
+            float: "right",
+            color: "#888",
+        },
+    }
+}
@@ -0,0 +1,335 @@
+use super::diff::{self, Line};
+use crate::{NoteVersion, Snapshot};
+use sauron::jss::jss;
+use sauron::prelude::*;
+use std::rc::Rc;
+use wasm_bindgen::closure::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+
+#[derive(Clone, Debug)]
+pub(crate) enum Msg {
+    Select(usize),
+    Restore,
+    Discard,
+    Close,
+}
+
+/// The events emitted to the app
+#[derive(Clone, Debug)]
+pub(crate) enum Event {
+    /// load the snapshot in the editor, together with its note on disk and its version
+    Restore(Snapshot, Option<(String, Option<NoteVersion>)>),
+    /// remove the snapshot
+    Discard(Snapshot),
+    Close,
+}
+
+struct Entry {
+    snapshot: Snapshot,
+    /// the note on disk and its version, None while it is loaded or when there is no such file
+    disk_note: Option<(String, Option<NoteVersion>)>,
+}
+
+/// A modal offering the unsaved buffers found at startup, showing their changes against
+/// the notes on disk
+pub(crate) struct Recovery<XMSG> {
+    entries: Vec<Entry>,
+    selected: usize,
+    listeners: Vec<Callback<Event, XMSG>>,
+}
+
+impl<XMSG> Recovery<XMSG> {
+    pub(crate) fn new(snapshots: Vec<Snapshot>) -> Self {
+        Self {
+            entries: snapshots
+                .into_iter()
+                .map(|snapshot| Entry {
+                    snapshot,
+                    disk_note: None,
+                })
+                .collect(),
+            selected: 0,
+            listeners: vec![],
+        }
+    }
+
+    pub(crate) fn on_event<F>(mut self, f: F) -> Self
+    where
+        F: Fn(Event) -> XMSG + 'static,
+    {
+        self.listeners.push(Callback::from(f));
+        self
+    }
+
+    pub(crate) fn set_disk_note(&mut self, note: &str, disk_note: (String, Option<NoteVersion>)) {
+        for entry in self.entries.iter_mut() {
+            if entry.snapshot.note.as_deref() == Some(note) {
+                entry.disk_note = Some(disk_note.clone());
+            }
+        }
+    }
+
+    /// the snapshot is discarded, returns true when there are no more snapshots
+    pub(crate) fn remove(&mut self, snapshot: &Snapshot) -> bool {
+        self.entries.retain(|entry| {
+            entry.snapshot.session != snapshot.session || entry.snapshot.note != snapshot.note
+        });
+        self.selected = self.selected.min(self.entries.len().saturating_sub(1));
+        self.entries.is_empty()
+    }
+
+    fn emit(&self, event: Event) -> Effects<Msg, XMSG> {
+        let xmsgs: Vec<XMSG> = self
+            .listeners
+            .iter()
+            .map(|listener| listener.emit(event.clone()))
+            .collect();
+        Effects::with_external(xmsgs)
+    }
+
+    fn view_diff(&self) -> Node<Msg> {
+        let entry = match self.entries.get(self.selected) {
+            Some(entry) => entry,
+            None => return span([], []),
+        };
+        let disk_content = entry
+            .disk_note
+            .as_ref()
+            .map(|(content, _)| content.as_str())
+            .unwrap_or_default();
+        pre(
+            [class("diff")],
+            diff::diff_lines(disk_content, &entry.snapshot.content)
+                .into_iter()
+                .map(|line| match line {
+                    Line::Same(line) => div([], [text!("  {}", line)]),
+                    Line::Added(line) => div([class("added")], [text!("+ {}", line)]),
+                    Line::Removed(line) => div([class("removed")], [text!("- {}", line)]),
+                }),
+        )
+    }
+}
+
+impl<XMSG> Component<Msg, XMSG> for Recovery<XMSG> {
+    fn update(&mut self, msg: Msg) -> Effects<Msg, XMSG> {
+        match msg {
+            Msg::Select(index) => {
+                self.selected = index;
+                Effects::none()
+            }
+            Msg::Restore => match self.entries.get(self.selected) {
+                Some(entry) => self.emit(Event::Restore(
+                    entry.snapshot.clone(),
+                    entry.disk_note.clone(),
+                )),
+                None => Effects::none(),
+            },
+            Msg::Discard => match self.entries.get(self.selected) {
+                Some(entry) => self.emit(Event::Discard(entry.snapshot.clone())),
+                None => Effects::none(),
+            },
+            Msg::Close => self.emit(Event::Close),
+        }
+    }
+
+    fn view(&self) -> Node<Msg> {
+        div(
+            [class("recovery")],
+            [div(
+                [class("recovery_dialog")],
+                [
+                    div(
+                        [class("recovery_header")],
+                        [
+                            text("Unsaved changes were recovered"),
+                            button([on_click(|_| Msg::Close)], [text("Later")]),
+                        ],
+                    ),
+                    ul(
+                        [],
+                        self.entries.iter().enumerate().map(|(index, entry)| {
+                            li(
+                                [
+                                    class(if index == self.selected {
+                                        "selected"
+                                    } else {
+                                        ""
+                                    }),
+                                    on_click(move |_| Msg::Select(index)),
+                                ],
+                                [
+                                    text(entry.snapshot.note.as_deref().unwrap_or("Untitled")),
+                                    span(
+                                        [class("saved_at")],
+                                        [text(time_string(entry.snapshot.saved_at))],
+                                    ),
+                                ],
+                            )
+                        }),
+                    ),
+                    self.view_diff(),
+                    div(
+                        [class("recovery_actions")],
+                        [
+                            button([on_click(|_| Msg::Restore)], [text("Restore")]),
+                            button([on_click(|_| Msg::Discard)], [text("Discard")]),
+                        ],
+                    ),
+                ],
+            )],
+        )
+    }
+
+    fn style(&self) -> String {
+        style()
+    }
+}
+
+/// the style does not depend on the state, so the app can include it even when the modal
+/// is not shown
+pub(crate) fn style() -> String {
+    jss! {
+        ".recovery": {
+            position: "fixed",
+            top: 0,
+            left: 0,
+            width: percent(100),
+            height: percent(100),
+            background_color: "rgba(0, 0, 0, 0.3)",
+            z_index: 1000,
+        },
+
+        ".recovery_dialog": {
+            width: px(700),
+            max_height: percent(80),
+            margin: format!("{} auto", percent(5)),
+            padding: px(10),
+            display: "flex",
+            flex_direction: "column",
+            background_color: "#fff",
+            border: format!("{} solid #ccc", px(1)),
+        },
+
+        ".recovery_header": {
+            display: "flex",
+            justify_content: "space-between",
+            padding_bottom: px(5),
+        },
+
+        ".recovery ul": {
+            padding: 0,
+            margin: 0,
+        },
+
+        ".recovery li": {
+            list_style: "none",
+            padding: px([5, 5]),
+            border_bottom: format!("{} solid #ddd", px(1)),
+            cursor: "default",
+        },
+
+        ".recovery li.selected": {
+            background_color: "#ddd",
+        },
+
+        ".recovery li .saved_at": {
+            float: "right",
+            color: "#888",
+        },
+
+        ".recovery .diff": {
+            flex: 1,
+            overflow: "auto",
+            padding: px(5),
+            border: format!("{} solid #ddd", px(1)),
+        },
+
+        ".recovery .diff .added": {
+            background_color: "#e6ffed",
+        },
+
+        ".recovery .diff .removed": {
+            background_color: "#ffeef0",
+        },
+
+        ".recovery_actions": {
+            display: "flex",
+            justify_content: "flex-end",
+            padding_top: px(5),
+        },
+    }
+}
+
+/// A new id for the snapshots of this session of the app, they do not replace the snapshots of
+/// the previous sessions which are not restored or discarded yet
+pub(crate) fn new_session() -> String {
+    let started_at = js_sys::Date::now() as u64;
+    let random = (js_sys::Math::random() * f64::from(u32::MAX)) as u32;
+    format!("{:x}{:08x}", started_at, random)
+}
+
+/// Dispatch the msg every interval, this is used to take the snapshots of the buffer.
+pub(crate) fn every<APP, MSG, F>(interval_ms: i32, tick: F) -> Cmd<APP, MSG>
+where
+    APP: Application<MSG> + 'static,
+    MSG: 'static,
+    F: Fn() -> MSG + 'static,
+{
+    let tick = Rc::new(tick);
+    Cmd::new(move |program| {
+        let window = match web_sys::window() {
+            Some(window) => window,
+            None => return,
+        };
+        let tick = tick.clone();
+        let on_tick: Closure<dyn FnMut()> = Closure::wrap(Box::new(move || {
+            program.dispatch(tick());
+        }));
+        if window
+            .set_interval_with_callback_and_timeout_and_arguments_0(
+                on_tick.as_ref().unchecked_ref(),
+                interval_ms,
+            )
+            .is_err()
+        {
+            log::error!("unable to start the timer");
+        }
+        // the timer is kept for the whole lifetime of the page
+        on_tick.forget();
+    })
+}
+
+/// Dispatch the msg once after the delay, this is used to wait for a pause in the typing.
+pub(crate) fn after<APP, MSG, F>(delay_ms: i32, msg: F) -> Cmd<APP, MSG>
+where
+    APP: Application<MSG> + 'static,
+    MSG: 'static,
+    F: Fn() -> MSG + 'static,
+{
+    let msg = Rc::new(msg);
+    Cmd::new(move |program| {
+        let window = match web_sys::window() {
+            Some(window) => window,
+            None => return,
+        };
+        let msg = msg.clone();
+        let on_timeout = Closure::once_into_js(move || {
+            program.dispatch(msg());
+        });
+        if window
+            .set_timeout_with_callback_and_timeout_and_arguments_0(
+                on_timeout.unchecked_ref(),
+                delay_ms,
+            )
+            .is_err()
+        {
+            log::error!("unable to start the timer");
+        }
+    })
+}
+
+/// the local date and time of the unix time in seconds
+fn time_string(unix_time: u64) -> String {
+    let millis = JsValue::from_f64(unix_time as f64 * 1000.0);
+    String::from(js_sys::Date::new(&millis).to_locale_string("default", &JsValue::UNDEFINED))
+}
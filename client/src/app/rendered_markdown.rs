@@ -1,21 +1,41 @@
+use crate::links;
 use plugins::Plugins;
 use sauron::prelude::*;
 use sauron_markdown::MarkdownParser;
-use std::cell::RefCell;
-use std::collections::BTreeMap;
+use source_map::Fence;
+pub(crate) use source_map::SourceMap;
+use std::cell::{Cell, RefCell};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 use std::rc::Rc;
 
 mod plugins;
+mod source_map;
 
 #[derive(Debug)]
 pub(crate) enum Msg {
     PluginMsg(Rc<RefCell<plugins::Plugins<Msg>>>, plugins::Msg),
+    /// the content was edited, the plugins of the fences which are kept keep their state
     ContentChanged(String),
+    /// the content of another note, the plugins of the previous content are all unmounted
+    NoteLoaded(String),
 }
 
 pub(crate) struct RenderedMarkdown<XMSG> {
+    /// the markdown with the relative links resolved against `note_dir`
     content: String,
+    /// the directory of the note relative to the workspace, empty when it is at the root
+    note_dir: String,
+    /// which lines of the content each rendered block comes from
+    source_map: SourceMap,
+    /// the rendered cells of the last view, keyed by the hash of their markdown
+    cells: RefCell<HashMap<u64, RenderedCell>>,
+    /// The id of the plugin component of each fence in the source map.
+    /// The ids are kept when the fences are edited or moved around.
+    fence_ids: Vec<usize>,
+    next_fence_id: usize,
     config: Config,
     plugin_context: Rc<RefCell<Context<Plugins<Msg>, Msg, plugins::Msg>>>,
     _phantom_msg: PhantomData<XMSG>,
@@ -34,6 +54,26 @@ impl Default for Config {
     }
 }
 
+struct RenderedCell {
+    node: Node<Msg>,
+    /// The ids of the plugin components in the cell. The view of a plugin changes with its
+    /// state, so its cell is rendered again when the plugin is updated.
+    plugin_ids: Vec<usize>,
+}
+
+/// The hooks of the components in the `Context`, which are called as their cells
+/// are added, edited and removed from the content
+pub(crate) trait Lifecycle {
+    /// the component is created for a cell which is rendered for the first time
+    fn mounted(&mut self) {}
+
+    /// the content of the cell of the component has changed
+    fn content_updated(&mut self, _content: &str) {}
+
+    /// the cell of the component is removed, and the component is dropped after this
+    fn unmounted(&mut self) {}
+}
+
 pub struct Context<COMP, MSG, CMSG> {
     components: BTreeMap<String, Rc<RefCell<COMP>>>,
     _phantom_msg: PhantomData<MSG>,
@@ -43,13 +83,92 @@ pub struct Context<COMP, MSG, CMSG> {
 impl<XMSG> RenderedMarkdown<XMSG> {
     pub(crate) fn from_str(content: &str) -> Self {
         let config = Config::default();
+        let source_map = SourceMap::from_markdown(content);
+        let fence_count = source_map.fences().len();
         Self {
             content: content.to_string(),
+            note_dir: String::new(),
+            source_map,
+            cells: RefCell::new(HashMap::new()),
+            fence_ids: (0..fence_count).collect(),
+            next_fence_id: fence_count,
             config: Config::default(),
             plugin_context: Rc::new(RefCell::new(Context::new())),
             _phantom_msg: PhantomData,
         }
     }
+
+    /// The relative links of a note in a sub directory are resolved against its directory,
+    /// since the page of the app is at the root of the workspace.
+    /// This applies from the next `ContentChanged`.
+    pub(crate) fn set_note_dir(&mut self, note_dir: &str) {
+        self.note_dir = note_dir.to_string();
+    }
+
+    fn resolve_links(&self, content: String) -> String {
+        if self.note_dir.is_empty() {
+            return content;
+        }
+        let resolved = links::rewrite_links(&content, |dest| {
+            if !links::is_local(dest) {
+                return None;
+            }
+            let (path, fragment) = links::split_fragment(dest);
+            if path.is_empty() {
+                return None;
+            }
+            Some(format!(
+                "/{}{}",
+                links::join_relative(&self.note_dir, path),
+                fragment
+            ))
+        });
+        match resolved {
+            Ok(resolved) => resolved,
+            Err(e) => {
+                log::warn!("the links are not resolved: {}", e);
+                content
+            }
+        }
+    }
+
+    pub(crate) fn source_map(&self) -> &SourceMap {
+        &self.source_map
+    }
+
+    /// the fences of the cell are rendered by the plugin components with these ids, in order
+    fn render_cell(&self, cell: &str, fence_ids: &[usize]) -> RenderedCell {
+        let plugin_ids_cell = RefCell::new(vec![]);
+        let plugin_ids = &plugin_ids_cell;
+        let fence_index = Cell::new(0);
+        let plugins = sauron_markdown::Plugins {
+            code_fence_processor: Some(Box::new(move |code_fence, code| {
+                let code_fence = code_fence.filter(|code_fence| !code_fence.trim().is_empty())?;
+                let fence_id = fence_ids.get(fence_index.replace(fence_index.get() + 1));
+                match fence_id {
+                    Some(fence_id) => {
+                        plugin_ids.borrow_mut().push(*fence_id);
+                        let mut plugin_context = self.plugin_context.borrow_mut();
+                        let view = plugin_context.map_view(
+                            fence_id,
+                            || Plugins::from_code_fence(code_fence, code, &self.config),
+                            Msg::PluginMsg,
+                        );
+                        Some(div([class(PLUGIN_CLASS)], [view]))
+                    }
+                    // the fence is not known to the source map
+                    None => Some(plugins::view_code_fence(code_fence, code, &self.config)),
+                }
+            })),
+            inline_html_processor: None,
+            tag_processor: None,
+        };
+        let node = MarkdownParser::with_plugins(cell, plugins).node();
+        RenderedCell {
+            node,
+            plugin_ids: plugin_ids_cell.into_inner(),
+        }
+    }
 }
 
 impl<XMSG> Component<Msg, XMSG> for RenderedMarkdown<XMSG> {
@@ -57,40 +176,82 @@ impl<XMSG> Component<Msg, XMSG> for RenderedMarkdown<XMSG> {
         log::trace!("---------> in rendered markdown component: {:?}", msg);
         match msg {
             Msg::ContentChanged(content) => {
-                self.content = content;
+                let content = self.resolve_links(content);
+                let source_map = SourceMap::from_markdown(&content);
+                let fence_ids = reconcile_fences(
+                    self.source_map.fences(),
+                    &self.fence_ids,
+                    source_map.fences(),
+                    &mut self.next_fence_id,
+                );
                 let mut plugin_context = self.plugin_context.borrow_mut();
-                //plugin_context.clear();
+                // the plugins of the removed fences are unmounted
+                plugin_context.evict(|comp_id| {
+                    fence_ids
+                        .iter()
+                        .any(|fence_id| fence_id.to_string() == comp_id)
+                });
+                for (fence_id, fence) in fence_ids.iter().zip(source_map.fences()) {
+                    plugin_context.update_content(fence_id, &fence.code);
+                }
+                self.source_map = source_map;
+                self.fence_ids = fence_ids;
+                self.content = content;
                 Effects::none()
             }
+            Msg::NoteLoaded(content) => {
+                self.plugin_context.borrow_mut().clear();
+                self.cells.borrow_mut().clear();
+                self.source_map = SourceMap::default();
+                self.fence_ids = vec![];
+                self.update(Msg::ContentChanged(content))
+            }
             Msg::PluginMsg(plugin, pmsg) => {
-                let effects =
-                    self.plugin_context
-                        .borrow_mut()
-                        .update_component(plugin, pmsg, Msg::PluginMsg);
+                let mut plugin_context = self.plugin_context.borrow_mut();
+                let comp_id = plugin_context.component_id(&plugin);
+                let effects = plugin_context.update_component(plugin, pmsg, Msg::PluginMsg);
+                // only the cell of the plugin is rendered again, an unmounted plugin has no cell
+                if let Some(comp_id) = comp_id {
+                    self.cells.borrow_mut().retain(|_, cell| {
+                        !cell
+                            .plugin_ids
+                            .iter()
+                            .any(|plugin_id| plugin_id.to_string() == comp_id)
+                    });
+                }
                 Effects::none()
             }
         }
     }
 
+    /// Each top level block is a cell which is rendered on its own,
+    /// only the cells which changed since the last view are rendered again.
     fn view(&self) -> Node<Msg> {
-        //TODO: there is no seamless way to update the plugins as the content of the raw code
-        //changes. We need to modify the generated markdown to create some sort of cells
-        //each, plugin boundary will be text cells
-        let plugins = sauron_markdown::Plugins {
-            code_fence_processor: Some(Box::new(move |code_fence, code| {
-                if let Some(code_fence) = code_fence {
-                    let mut plugin_context = self.plugin_context.borrow_mut();
-                    let plugin = Plugins::from_code_fence(code_fence, code, &self.config);
-                    Some(plugin_context.map_view(code_fence, plugin, Msg::PluginMsg))
-                } else {
-                    None
-                }
-            })),
-            inline_html_processor: None,
-            tag_processor: None,
-        };
-        let md_parser = MarkdownParser::with_plugins(&self.content, plugins);
-        md_parser.node()
+        let mut previous = self.cells.take();
+        let mut cells = HashMap::new();
+        let nodes = self
+            .source_map
+            .cells(&self.content)
+            .iter()
+            .map(|(cell, fences)| {
+                let fence_ids = &self.fence_ids[fences.clone()];
+                let hash = cell_hash(cell, fence_ids);
+                let rendered = match previous.remove(&hash) {
+                    Some(rendered) => rendered,
+                    None => match cells.remove(&hash) {
+                        // the same cell is repeated in the content
+                        Some(rendered) => rendered,
+                        None => self.render_cell(cell, fence_ids),
+                    },
+                };
+                let node = div([class("cell")], [rendered.node.clone()]);
+                cells.insert(hash, rendered);
+                node
+            })
+            .collect::<Vec<_>>();
+        // the cells which are no longer in the content are dropped
+        self.cells.replace(cells);
+        div([class("cells")], nodes)
     }
 
     fn style(&self) -> String {
@@ -98,9 +259,77 @@ impl<XMSG> Component<Msg, XMSG> for RenderedMarkdown<XMSG> {
     }
 }
 
+/// Render the markdown into a node which doesn't need the wasm runtime,
+/// the plugins are rendered without their interactive components.
+pub(crate) fn render_static<MSG>(content: &str, config: &Config) -> Node<MSG> {
+    let plugins = sauron_markdown::Plugins {
+        code_fence_processor: Some(Box::new(move |code_fence, code| {
+            code_fence.map(|code_fence| plugins::view_code_fence(code_fence, code, config))
+        })),
+        inline_html_processor: None,
+        tag_processor: None,
+    };
+    MarkdownParser::with_plugins(content, plugins).node()
+}
+
+/// cells with the same markdown are still rendered by different plugin components
+fn cell_hash(cell: &str, fence_ids: &[usize]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    cell.hash(&mut hasher);
+    fence_ids.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// Give each fence the id of its previous fence, so the plugins keep their state.
+/// An unchanged fence keeps its id even when it is moved, then an edited fence takes
+/// the id of the next unmatched fence of the same language. New fences get new ids.
+fn reconcile_fences(
+    previous: &[Fence],
+    previous_ids: &[usize],
+    fences: &[Fence],
+    next_id: &mut usize,
+) -> Vec<usize> {
+    let mut is_matched = vec![false; previous.len()];
+    let mut ids: Vec<Option<usize>> = fences
+        .iter()
+        .map(|fence| {
+            let index = (0..previous.len())
+                .find(|&index| !is_matched[index] && previous[index] == *fence)?;
+            is_matched[index] = true;
+            previous_ids.get(index).copied()
+        })
+        .collect();
+    for (fence, id) in fences.iter().zip(ids.iter_mut()) {
+        if id.is_none() {
+            let index = (0..previous.len())
+                .find(|&index| !is_matched[index] && previous[index].language == fence.language);
+            if let Some(index) = index {
+                is_matched[index] = true;
+                *id = previous_ids.get(index).copied();
+            }
+        }
+    }
+    ids.into_iter()
+        .map(|id| {
+            id.unwrap_or_else(|| {
+                *next_id += 1;
+                *next_id - 1
+            })
+        })
+        .collect()
+}
+
+/// the container of each plugin component, whose clicks are handled by the plugin
+pub(crate) const PLUGIN_CLASS: &str = "plugin";
+
+/// the styles used by the plugins
+pub(crate) fn style() -> String {
+    plugins::style()
+}
+
 impl<COMP, MSG, CMSG> Context<COMP, MSG, CMSG>
 where
-    COMP: Component<CMSG, MSG> + 'static,
+    COMP: Component<CMSG, MSG> + Lifecycle + 'static,
     MSG: 'static,
     CMSG: 'static,
 {
@@ -117,15 +346,13 @@ where
     /// at every view call. This should unique such that it can re-use the existing
     /// component from previous view call. Don't use random unique, otherwise will be
     /// re-crated at every view call.
-    fn map_view<F>(&mut self, comp_id: impl ToString, component: COMP, mapper: F) -> Node<MSG>
+    /// The component is only created when there is none with this id yet.
+    fn map_view<C, F>(&mut self, comp_id: impl ToString, create: C, mapper: F) -> Node<MSG>
     where
+        C: FnOnce() -> COMP,
         F: Fn(Rc<RefCell<COMP>>, CMSG) -> MSG + 'static,
     {
-        log::trace!(
-            "{} component_id: {:?}",
-            comp_id.to_string(),
-            component.get_component_id(),
-        );
+        log::trace!("component_id: {}", comp_id.to_string());
         if let Some(component) = self.components.get(&comp_id.to_string()) {
             let component_clone = component.clone();
             component
@@ -133,7 +360,8 @@ where
                 .view()
                 .map_msg(move |cmsg| mapper(component_clone.clone(), cmsg))
         } else {
-            let component = Rc::new(RefCell::new(component));
+            let component = Rc::new(RefCell::new(create()));
+            component.borrow_mut().mounted();
             let component_clone = component.clone();
             let view = component
                 .borrow()
@@ -144,6 +372,40 @@ where
         }
     }
 
+    /// the id of the component, None if it was unmounted
+    fn component_id(&self, component: &Rc<RefCell<COMP>>) -> Option<String> {
+        self.components
+            .iter()
+            .find(|(_, mounted)| Rc::ptr_eq(mounted, component))
+            .map(|(comp_id, _)| comp_id.clone())
+    }
+
+    /// pass the new content to the component, if it is mounted
+    fn update_content(&mut self, comp_id: impl ToString, content: &str) {
+        if let Some(component) = self.components.get(&comp_id.to_string()) {
+            component.borrow_mut().content_updated(content);
+        }
+    }
+
+    /// unmount and drop the components which ids don't satisfy the predicate
+    fn evict<F>(&mut self, keep: F)
+    where
+        F: Fn(&str) -> bool,
+    {
+        let evicted: Vec<String> = self
+            .components
+            .keys()
+            .filter(|comp_id| !keep(comp_id))
+            .cloned()
+            .collect();
+        for comp_id in evicted {
+            if let Some(component) = self.components.remove(&comp_id) {
+                log::trace!("unmounting component {}", comp_id);
+                component.borrow_mut().unmounted();
+            }
+        }
+    }
+
     fn update_component<F>(
         &mut self,
         component: Rc<RefCell<COMP>>,
@@ -164,6 +426,6 @@ where
     }
 
     fn clear(&mut self) {
-        self.components.clear();
+        self.evict(|_| false);
     }
 }
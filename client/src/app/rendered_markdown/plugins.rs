@@ -1,4 +1,4 @@
-use crate::app::rendered_markdown::Config;
+use crate::app::rendered_markdown::{Config, Lifecycle};
 use rune_script::RuneScript;
 use sauron::prelude::*;
 use std::marker::PhantomData;
@@ -32,68 +32,86 @@ impl<XMSG> Plugins<XMSG> {
             _phantom_msg: PhantomData,
         }
     }
+    /// the editor of a rune script is only created once the plugin is mounted
     pub(crate) fn from_code_fence(code_fence: &str, content: &str, config: &Config) -> Self {
         Self {
             code_fence: code_fence.to_string(),
             content: content.to_string(),
             config: config.clone(),
-            rune_script: if code_fence == "rune" {
-                Some(RuneScript::from_str(content, config))
-            } else {
-                None
-            },
+            rune_script: None,
             _phantom_msg: PhantomData,
         }
     }
 }
 
+impl<XMSG> Lifecycle for Plugins<XMSG> {
+    /// the editor of the rune script is created when it is first shown, or shown again after
+    /// it was unmounted
+    fn mounted(&mut self) {
+        log::trace!("mounted the {} plugin", self.code_fence);
+        if self.code_fence == "rune" && self.rune_script.is_none() {
+            self.rune_script = Some(RuneScript::from_str(&self.content, &self.config));
+        }
+    }
+
+    /// the code of the fence was edited, the plugin keeps the rest of its state
+    fn content_updated(&mut self, content: &str) {
+        if self.content != content {
+            self.content = content.to_string();
+            if let Some(rune_script) = self.rune_script.as_mut() {
+                rune_script.set_script(content);
+            }
+        }
+    }
+
+    /// the fence is removed from the note, the editor of the rune script is released
+    fn unmounted(&mut self) {
+        log::trace!("unmounted the {} plugin", self.code_fence);
+        self.rune_script = None;
+    }
+}
+
 impl<XMSG> Component<Msg, XMSG> for Plugins<XMSG> {
+    /// the msgs of a rune script which was unmounted are dropped
     fn update(&mut self, msg: Msg) -> Effects<Msg, XMSG> {
-        match &*self.code_fence {
-            "rune" => {
-                if let Msg::RuneScriptMsg(rmsg) = msg {
-                    if let Some(rune_script) = self.rune_script.as_mut() {
-                        let effects = rune_script.update(rmsg);
-                        let (local, _external) = effects.localize(Msg::RuneScriptMsg).unzip();
-                        Effects::new(local, [])
-                    } else {
-                        Effects::none()
-                    }
-                } else {
-                    unreachable!()
-                }
+        let Msg::RuneScriptMsg(rmsg) = msg;
+        match self.rune_script.as_mut() {
+            Some(rune_script) => {
+                let effects = rune_script.update(rmsg);
+                let (local, _external) = effects.localize(Msg::RuneScriptMsg).unzip();
+                Effects::new(local, [])
             }
-            _ => Effects::none(),
+            None => Effects::none(),
         }
     }
 
+    /// a rune script which is not mounted is shown as highlighted code
     fn view(&self) -> Node<Msg> {
-        match &*self.code_fence {
-            "bob" => svgbob_plugin::convert_svgbob(&self.content),
-            "{side-to-side.bob}" => svgbob_plugin::side_to_side_bob(&self.content),
-            "sh" | "bash" => {
-                fake_terminal::fake_terminal(&self.content, &self.code_fence, &self.config)
-            }
-            "warning" => admonition::warning(&self.content),
-            "info" => admonition::info(&self.content),
-            "note" => admonition::note(&self.content),
-            "rune" => {
-                //let rune_script = RuneScript::from_str(&self.content, &self.config);
-                if let Some(rune_script) = &self.rune_script {
-                    rune_script.view().map_msg(Msg::RuneScriptMsg)
-                } else {
-                    unreachable!()
-                }
-            }
-            _ => ultron_ssg::render(
-                &self.content,
-                &self.code_fence,
-                Some(&self.config.highlight_theme),
-            ),
+        match &self.rune_script {
+            Some(rune_script) => rune_script.view().map_msg(Msg::RuneScriptMsg),
+            None => view_code_fence(&self.code_fence, &self.content, &self.config),
         }
     }
 
     fn style(&self) -> String {
-        [fake_terminal::style(), admonition::style()].join("\n")
+        style()
     }
 }
+
+/// render the code fence without the interactive components,
+/// rune scripts are displayed as highlighted code
+pub(crate) fn view_code_fence<MSG>(code_fence: &str, content: &str, config: &Config) -> Node<MSG> {
+    match code_fence {
+        "bob" => svgbob_plugin::convert_svgbob(content),
+        "{side-to-side.bob}" => svgbob_plugin::side_to_side_bob(content),
+        "sh" | "bash" => fake_terminal::fake_terminal(content, code_fence, config),
+        "warning" => admonition::warning(content),
+        "info" => admonition::info(content),
+        "note" => admonition::note(content),
+        _ => ultron_ssg::render(content, code_fence, Some(&config.highlight_theme)),
+    }
+}
+
+pub(crate) fn style() -> String {
+    [fake_terminal::style(), admonition::style()].join("\n")
+}
@@ -38,6 +38,16 @@ impl<XMSG> fmt::Debug for RuneScript<XMSG> {
 
 impl<XMSG> RuneScript<XMSG> {
     pub(crate) fn from_str(script: &str, config: &Config) -> Self {
+        Self {
+            editor: Self::create_editor(script),
+            script: script.to_string(),
+            config: config.clone(),
+            output: None,
+            _phantom_msg: PhantomData,
+        }
+    }
+
+    fn create_editor(script: &str) -> Editor<Msg> {
         let options = ultron::Options {
             use_block_mode: false,
             show_line_numbers: false,
@@ -46,14 +56,15 @@ impl<XMSG> RuneScript<XMSG> {
             syntax_token: "rune".to_string(),
             ..Default::default()
         };
+        Editor::from_str(options, script).on_change(Msg::ScriptChanged)
+    }
 
-        Self {
-            editor: Editor::from_str(options, script).on_change(Msg::ScriptChanged),
-            script: script.to_string(),
-            config: config.clone(),
-            output: None,
-            _phantom_msg: PhantomData,
-        }
+    /// the script was edited in the note, the output of the last run is cleared since it is
+    /// not the output of this script
+    pub(crate) fn set_script(&mut self, script: &str) {
+        self.script = script.to_string();
+        self.editor = Self::create_editor(script);
+        self.output = None;
     }
 
     fn execute_script(script: &str) -> runestick::Result<String> {
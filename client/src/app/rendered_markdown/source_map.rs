@@ -0,0 +1,264 @@
+//! Map the lines of the markdown source to the top level blocks it is rendered into,
+//! such as the paragraphs, headings, lists and code fences.
+use pulldown_cmark::{CodeBlockKind, Event, Options, Parser, Tag};
+use std::ops::Range;
+
+#[derive(Debug, Default)]
+pub(crate) struct SourceMap {
+    /// the top level blocks, in the order they are rendered
+    blocks: Vec<Block>,
+    /// The link reference definitions, which are not part of any block.
+    /// Each cell needs them, since a cell is rendered on its own.
+    definitions: String,
+    /// The label and the markdown of the footnote definitions, which are not part of any block
+    /// either. They are rendered with the cells which refer to them.
+    footnotes: Vec<(String, String)>,
+    /// the code fences with a language, which are rendered by the plugins
+    fences: Vec<Fence>,
+}
+
+#[derive(Debug)]
+struct Block {
+    lines: Range<usize>,
+    /// the byte range of the block in the content
+    source: Range<usize>,
+    /// the indexes of the code fences in this block
+    fences: Range<usize>,
+    /// the labels of the footnotes this block refers to
+    footnotes: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Fence {
+    pub(crate) language: String,
+    pub(crate) code: String,
+}
+
+impl SourceMap {
+    pub(crate) fn from_markdown(content: &str) -> Self {
+        let line_starts: Vec<usize> = std::iter::once(0)
+            .chain(content.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        let line_of = |offset: usize| match line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(line) => line - 1,
+        };
+        let mut blocks: Vec<Block> = vec![];
+        let mut definitions = String::new();
+        let mut footnotes = vec![];
+        let mut fences = vec![];
+        // the first fence of the current block
+        let mut first_fence = 0;
+        // the footnotes the current block refers to
+        let mut block_footnotes = vec![];
+        // the fence which the text events are the code of
+        let mut in_fence = false;
+        // the code of the fences in the footnote definitions is not rendered by the plugins,
+        // since they are appended after the fences of the cell
+        let mut in_footnote = false;
+        // an html block is one event per line, which are merged into one block
+        let mut in_html = false;
+        // the end of the last block, anything in between blocks is a link reference definition
+        let mut last_end = 0;
+        let mut depth = 0;
+        for (event, range) in Parser::new_ext(content, Options::all()).into_offset_iter() {
+            if depth == 0 {
+                first_fence = fences.len();
+                block_footnotes = vec![];
+            }
+            match &event {
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(language)))
+                    if !language.trim().is_empty() && !in_footnote =>
+                {
+                    fences.push(Fence {
+                        language: language.trim().to_string(),
+                        code: String::new(),
+                    });
+                    in_fence = true;
+                }
+                Event::End(Tag::CodeBlock(_)) => in_fence = false,
+                Event::Text(code) if in_fence => {
+                    if let Some(fence) = fences.last_mut() {
+                        fence.code.push_str(code);
+                    }
+                }
+                Event::Start(Tag::FootnoteDefinition(_)) => in_footnote = true,
+                Event::FootnoteReference(label) => block_footnotes.push(label.to_string()),
+                _ => (),
+            }
+            match event {
+                Event::Start(_) => depth += 1,
+                Event::End(_) => depth -= 1,
+                _ => (),
+            }
+            // the end of a block, or a block which has no start and end such as a rule
+            let is_block = match event {
+                Event::End(_) => depth == 0,
+                Event::Start(_) => false,
+                _ => depth == 0,
+            };
+            if !is_block {
+                continue;
+            }
+            // the range of a block includes its trailing newline
+            let end = line_of(range.end.saturating_sub(1).max(range.start)) + 1;
+            let is_html = matches!(event, Event::Html(_));
+            match blocks.last_mut() {
+                Some(block) if is_html && in_html && block.source.end == range.start => {
+                    block.lines.end = end;
+                    block.source.end = range.end;
+                    last_end = range.end;
+                    continue;
+                }
+                _ => (),
+            }
+            in_html = is_html;
+            push_definitions(
+                &mut definitions,
+                &content[last_end.min(range.start)..range.start],
+            );
+            last_end = range.end;
+            if let Event::End(Tag::FootnoteDefinition(label)) = event {
+                in_footnote = false;
+                footnotes.push((label.to_string(), content[range].trim().to_string()));
+                continue;
+            }
+            blocks.push(Block {
+                lines: line_of(range.start)..end,
+                source: range,
+                fences: first_fence..fences.len(),
+                footnotes: std::mem::take(&mut block_footnotes),
+            });
+        }
+        push_definitions(&mut definitions, &content[last_end.min(content.len())..]);
+        Self {
+            blocks,
+            definitions,
+            footnotes,
+            fences,
+        }
+    }
+
+    pub(crate) fn fences(&self) -> &[Fence] {
+        &self.fences
+    }
+
+    /// The markdown of each top level block, which can be rendered on its own.
+    /// The link reference definitions of the content are appended to each cell, together with
+    /// the footnote definitions the cell refers to.
+    /// The fences of each cell are indexes into `fences`.
+    pub(crate) fn cells(&self, content: &str) -> Vec<(String, Range<usize>)> {
+        self.blocks
+            .iter()
+            .map(|block| {
+                let mut cell = content[block.source.clone()].to_string();
+                if !self.definitions.is_empty() {
+                    cell.push_str("\n\n");
+                    cell.push_str(&self.definitions);
+                }
+                let footnotes = self
+                    .footnotes
+                    .iter()
+                    .filter(|(label, _)| block.footnotes.contains(label));
+                for (_, footnote) in footnotes {
+                    cell.push_str("\n\n");
+                    cell.push_str(footnote);
+                    cell.push('\n');
+                }
+                (cell, block.fences.clone())
+            })
+            .collect()
+    }
+
+    /// The block at this line and how far into the block the line is, from 0.0 to 1.0.
+    /// A line in between blocks belongs to the start of the next block.
+    pub(crate) fn block_at_line(&self, line: f64) -> Option<(usize, f64)> {
+        let index = self
+            .blocks
+            .iter()
+            .position(|block| line < block.lines.end as f64)
+            .unwrap_or(self.blocks.len().checked_sub(1)?);
+        let lines = &self.blocks[index].lines;
+        let fraction = (line - lines.start as f64) / lines.len().max(1) as f64;
+        Some((index, fraction.clamp(0.0, 1.0)))
+    }
+
+    /// the line at this fraction of the block, the reverse of `block_at_line`
+    pub(crate) fn line_in_block(&self, index: usize, fraction: f64) -> Option<f64> {
+        let lines = &self.blocks.get(index)?.lines;
+        Some(lines.start as f64 + fraction * lines.len() as f64)
+    }
+}
+
+fn push_definitions(definitions: &mut String, gap: &str) {
+    let gap = gap.trim();
+    if !gap.is_empty() {
+        definitions.push_str(gap);
+        definitions.push('\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cells(content: &str) -> Vec<String> {
+        SourceMap::from_markdown(content)
+            .cells(content)
+            .into_iter()
+            .map(|(cell, _)| cell)
+            .collect()
+    }
+
+    #[test]
+    fn each_block_is_a_cell() {
+        assert_eq!(
+            cells("# Title\n\nsome text\n\n---\n"),
+            vec!["# Title\n", "some text\n", "---\n"]
+        );
+    }
+
+    #[test]
+    fn link_definitions_are_in_each_cell() {
+        assert_eq!(
+            cells("a [link]\n\n[link]: a.md\n\nb [link]\n"),
+            vec![
+                "a [link]\n\n\n[link]: a.md\n",
+                "b [link]\n\n\n[link]: a.md\n"
+            ]
+        );
+    }
+
+    #[test]
+    fn html_lines_are_one_cell() {
+        let content = "<div>\nx\n</div>\n\n<p>y</p>\n\ntext\n";
+        assert_eq!(
+            cells(content),
+            vec!["<div>\nx\n</div>\n", "<p>y</p>\n", "text\n"]
+        );
+        let source_map = SourceMap::from_markdown(content);
+        assert_eq!(source_map.block_at_line(2.0), Some((0, 2.0 / 3.0)));
+        assert_eq!(source_map.block_at_line(4.0), Some((1, 0.0)));
+    }
+
+    #[test]
+    fn footnotes_are_in_the_cells_which_refer_to_them() {
+        assert_eq!(
+            cells("a[^1]\n\nb\n\n[^1]: the note\n"),
+            vec!["a[^1]\n\n\n[^1]: the note\n", "b\n"]
+        );
+    }
+
+    #[test]
+    fn fences_of_each_cell() {
+        let content = "```bob\n-->\n```\n\ntext\n\n```rune\n1\n```\n\n```rune\n2\n```\n";
+        let source_map = SourceMap::from_markdown(content);
+        let fences: Vec<Range<usize>> = source_map
+            .cells(content)
+            .into_iter()
+            .map(|(_, fences)| fences)
+            .collect();
+        assert_eq!(fences, vec![0..1, 1..1, 1..2, 2..3]);
+        assert_eq!(source_map.fences()[2].code, "2\n");
+    }
+}
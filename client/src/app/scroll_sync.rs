@@ -0,0 +1,152 @@
+//! Keep the editor and the rendered markdown scrolled to the same block of the note,
+//! and find the block which is clicked in the rendered markdown
+use crate::app::rendered_markdown::{SourceMap, PLUGIN_CLASS};
+use wasm_bindgen::JsCast;
+use web_sys::{Element, Event};
+
+const EDITOR_SELECTOR: &str = ".app .editor";
+const PREVIEW_SELECTOR: &str = ".app .rendered_markdown";
+/// each top level block is rendered in its own cell
+const CELL_SELECTOR: &str = ".cells > .cell";
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub(crate) enum Pane {
+    Editor,
+    Preview,
+}
+
+/// Scroll the rendered markdown to the block at the top of the editor.
+/// This returns the new scroll top of the rendered markdown, None when it did not move.
+pub(crate) fn sync_preview(
+    source_map: &SourceMap,
+    line_count: usize,
+    editor_scroll_top: i32,
+) -> Option<i32> {
+    let editor = find(EDITOR_SELECTOR)?;
+    let preview = find(PREVIEW_SELECTOR)?;
+    let line = editor_scroll_top as f64 / line_height(&editor, line_count);
+    let (index, fraction) = source_map.block_at_line(line)?;
+    let (top, height) = *rendered_blocks(&preview).get(index)?;
+    scroll_to(&preview, top + fraction * height)
+}
+
+/// Scroll the editor to the lines of the block at the top of the rendered markdown.
+/// This returns the new scroll top of the editor, None when it did not move.
+pub(crate) fn sync_editor(
+    source_map: &SourceMap,
+    line_count: usize,
+    preview_scroll_top: i32,
+) -> Option<i32> {
+    let editor = find(EDITOR_SELECTOR)?;
+    let preview = find(PREVIEW_SELECTOR)?;
+    let scroll_top = preview_scroll_top as f64;
+    let blocks = rendered_blocks(&preview);
+    let index = blocks
+        .iter()
+        .rposition(|(top, _)| *top <= scroll_top)
+        .unwrap_or(0);
+    let (top, height) = *blocks.get(index)?;
+    let fraction = if height > 0.0 {
+        ((scroll_top - top) / height).min(1.0)
+    } else {
+        0.0
+    };
+    let line = source_map.line_in_block(index, fraction)?;
+    scroll_to(&editor, line * line_height(&editor, line_count))
+}
+
+/// Scroll the editor to show this line, when it is not visible yet.
+/// This returns the new scroll top of the editor, None when it did not move.
+pub(crate) fn reveal_line(line: usize, line_count: usize) -> Option<i32> {
+    let editor = find(EDITOR_SELECTOR)?;
+    let line_height = line_height(&editor, line_count);
+    let top = line as f64 * line_height;
+    let scroll_top = editor.scroll_top() as f64;
+    let client_height = editor.client_height() as f64;
+    if top < scroll_top || top + line_height > scroll_top + client_height {
+        // the line is placed at the middle of the editor
+        scroll_to(&editor, top - client_height / 2.0)
+    } else {
+        None
+    }
+}
+
+/// the index of the rendered block at this vertical position of the window
+pub(crate) fn rendered_block_at(client_y: i32) -> Option<usize> {
+    let preview = find(PREVIEW_SELECTOR)?;
+    let y = client_y as f64 - origin(&preview);
+    rendered_blocks(&preview)
+        .iter()
+        .position(|(top, height)| y >= *top && y < top + height)
+}
+
+/// Whether the event is on an element which handles its own clicks, such as a link or a plugin,
+/// the editor is not moved to the block of those clicks
+pub(crate) fn is_interactive(event: &Event) -> bool {
+    let selector = format!(
+        "a, button, input, select, textarea, label, summary, [contenteditable], .{}",
+        PLUGIN_CLASS
+    );
+    event
+        .target()
+        .and_then(|target| target.dyn_into::<Element>().ok())
+        .and_then(|element| element.closest(&selector).ok().flatten())
+        .is_some()
+}
+
+/// the css selector of the rendered block at this index
+pub(crate) fn rendered_block_selector(index: usize) -> String {
+    format!(
+        "{} {}:nth-child({})",
+        PREVIEW_SELECTOR,
+        CELL_SELECTOR,
+        index + 1
+    )
+}
+
+fn find(selector: &str) -> Option<Element> {
+    web_sys::window()?
+        .document()?
+        .query_selector(selector)
+        .ok()
+        .flatten()
+}
+
+fn scroll_to(element: &Element, scroll_top: f64) -> Option<i32> {
+    let before = element.scroll_top();
+    element.set_scroll_top(scroll_top.round() as i32);
+    // the browser clamps the scroll top to the scrollable height
+    let after = element.scroll_top();
+    if after != before {
+        Some(after)
+    } else {
+        None
+    }
+}
+
+/// the lines of the editor all have the same height, since it has no wrapping
+fn line_height(editor: &Element, line_count: usize) -> f64 {
+    editor.scroll_height() as f64 / line_count.max(1) as f64
+}
+
+/// the top of each rendered block and its height, relative to the scrolled content
+fn rendered_blocks(preview: &Element) -> Vec<(f64, f64)> {
+    let blocks = match preview.query_selector_all(CELL_SELECTOR) {
+        Ok(blocks) => blocks,
+        Err(_) => return vec![],
+    };
+    let origin = origin(preview);
+    (0..blocks.length())
+        .filter_map(|i| blocks.item(i))
+        .filter_map(|block| block.dyn_into::<Element>().ok())
+        .map(|block| {
+            let rect = block.get_bounding_client_rect();
+            (rect.top() - origin, rect.height())
+        })
+        .collect()
+}
+
+/// the vertical position in the window of the top of the scrolled content
+fn origin(preview: &Element) -> f64 {
+    preview.get_bounding_client_rect().top() - preview.scroll_top() as f64
+}
@@ -0,0 +1,127 @@
+//! The commands which the client sends to the desktop app through `invoke`,
+//! and the responses which are sent back through `commandCallback`.
+use crate::NoteVersion;
+use serde::{Deserialize, Serialize};
+
+/// A command executed natively by the desktop app
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum Command {
+    /// show the open file dialog and read the selected note
+    OpenFile,
+    /// write the content to the path, the save dialog is shown when there is no path
+    SaveFile {
+        path: Option<String>,
+        content: String,
+        /// the version of the file when it was loaded, it is not overwritten when it was
+        /// modified since
+        version: Option<NoteVersion>,
+    },
+    /// open a file without showing the dialog, such as a recent file or the file passed on
+    /// the command line
+    OpenPath {
+        path: String,
+    },
+    /// the files recently opened or saved
+    ListRecent,
+    ClearRecent,
+    /// read a file relative to the workspace
+    ReadWorkspaceFile {
+        path: String,
+    },
+    /// read a file without adding it to the recent files
+    ReadFile {
+        path: String,
+    },
+    /// show a native message box
+    ShowMessage {
+        title: String,
+        message: String,
+    },
+    /// set the title of the window, the app title is appended to it
+    SetTitle {
+        title: String,
+    },
+    /// the installed license, if it is valid
+    LicenseStatus,
+    /// verify the license then install it
+    InstallLicense {
+        license: String,
+    },
+    /// the changes of the buffer which are not saved yet, the content is None once they are.
+    /// They are offered to be saved when the window is closed, since there is no
+    /// `beforeunload` event when the window of the webview is closed.
+    Unsaved {
+        /// the session of the app, the changes are kept as its snapshot when they are not saved
+        session: String,
+        note: Option<String>,
+        content: Option<String>,
+        version: Option<NoteVersion>,
+    },
+}
+
+/// The command together with the callback which will receive the response
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Invocation {
+    pub callback_id: usize,
+    pub command: Command,
+}
+
+/// The successful result of a command
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum CommandOutput {
+    /// the file picked in the open dialog and its content
+    Opened {
+        path: String,
+        content: String,
+        version: NoteVersion,
+    },
+    /// the user closed the dialog without picking a file
+    Cancelled,
+    Saved {
+        path: String,
+        version: NoteVersion,
+    },
+    Recent {
+        paths: Vec<String>,
+    },
+    FileContent {
+        path: String,
+        content: String,
+        version: NoteVersion,
+    },
+    /// the installed license, None if there is no valid license
+    License {
+        license: Option<LicenseInfo>,
+    },
+    /// the command has no output
+    Done,
+}
+
+/// The verified license of the desktop app
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LicenseInfo {
+    pub licensee: String,
+    /// the unix time in seconds when the license expires, None if it never expires
+    pub expires_at: Option<u64>,
+}
+
+/// The error of a command, this has the same fields as the json error body of the server
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CommandError {
+    /// a stable name of the error such as `not_found` or `forbidden`
+    pub error: String,
+    pub message: String,
+}
+
+pub type CommandResult = Result<CommandOutput, CommandError>;
+
+impl CommandError {
+    pub fn new(error: &str, message: impl ToString) -> Self {
+        Self {
+            error: error.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
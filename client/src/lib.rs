@@ -7,8 +7,19 @@ use ultron::editor;
 use ultron::editor::Editor;
 use ultron::nalgebra::Point2;
 
+pub use command::{Command, CommandError, CommandOutput, CommandResult, Invocation, LicenseInfo};
+pub use note_entry::NoteEntry;
+pub use note_event::NoteEvent;
+pub use note_version::{NoteVersion, MODIFIED_HEADER};
+pub use snapshot::Snapshot;
 pub use ultron::nalgebra;
 mod app;
+mod command;
+pub mod links;
+mod note_entry;
+mod note_event;
+mod note_version;
+mod snapshot;
 
 pub const APP_CONTAINER: &str = "app_container";
 pub const APP_TITLE: &str = "Madnotes";
@@ -16,12 +27,17 @@ pub const APP_JS_FILE: &str = "./pkg/client.js";
 pub const APP_WASM_FILE: &str = "./pkg/client_bg.wasm";
 pub const FAVICON_ICO: &str = "favicon.ico";
 
+/// the attribute of the app container which contains the note to be opened at startup
+pub const OPEN_NOTE_ATTRIBUTE: &str = "data-open_note";
+
 #[derive(Clone)]
 pub struct Settings {
     pub app_container: &'static str,
     pub app_title: String,
     pub app_js_file: &'static str,
     pub app_wasm_file: &'static str,
+    /// the note to be loaded at startup, relative to the workspace
+    pub open_note: Option<String>,
 }
 
 impl Default for Settings {
@@ -31,14 +47,20 @@ impl Default for Settings {
             app_title: APP_TITLE.to_string(),
             app_js_file: APP_JS_FILE,
             app_wasm_file: APP_WASM_FILE,
+            open_note: None,
         }
     }
 }
 
-#[cfg(feature = "external-invoke")]
-#[wasm_bindgen]
-extern "C" {
-    fn invoke(arg: &str);
+/// Render the markdown together with the output of its plugins into a node,
+/// which can be rendered to a string natively without the wasm runtime.
+pub fn render_markdown<MSG>(content: &str) -> Node<MSG> {
+    app::render_markdown(content)
+}
+
+/// the css needed to display the nodes from `render_markdown`
+pub fn markdown_style() -> String {
+    app::markdown_style()
 }
 
 #[wasm_bindgen(start)]
@@ -49,9 +71,15 @@ pub fn main() {
         .get_element_by_id(APP_CONTAINER)
         .expect("must have the #app_container in the page::index");
 
-    let content = MARKDOWN_EXAMPLE;
-    //let content = ""; // it would crash when using the desktop-app when content is preloaded with long text
-    Program::replace_mount(App::with_content(content), &app_container);
+    let app = match app_container.get_attribute(OPEN_NOTE_ATTRIBUTE) {
+        Some(note) => App::with_note(&note),
+        None => {
+            let content = MARKDOWN_EXAMPLE;
+            //let content = ""; // it would crash when using the desktop-app when content is preloaded with long text
+            App::with_content(content)
+        }
+    };
+    Program::replace_mount(app, &app_container);
 }
 
 const MARKDOWN_EXAMPLE: &str = r#"
@@ -0,0 +1,396 @@
+//! Find and rewrite the destinations of the links and images in a note
+use pulldown_cmark::{Event, LinkType, Options, Parser, Tag};
+use std::ops::Range;
+
+/// A link or an image destination in the markdown
+#[derive(Debug)]
+pub struct Reference {
+    /// the destination, with its escapes resolved
+    pub dest: String,
+    /// the byte range of the destination in the markdown
+    pub range: Range<usize>,
+}
+
+/// The destination of a link could not be found in the markdown,
+/// so it can neither be checked nor rewritten
+#[derive(Debug, thiserror::Error)]
+#[error("unable to locate the link destination: {0}")]
+pub struct UnlocatedLink(String);
+
+/// the attributes in html elements which refers to other files
+const HTML_ATTRIBUTES: [&str; 3] = ["src=", "href=", "data="];
+
+/// Find the destination of links and images, ie: `[text](dest)` and `![alt](dest)`,
+/// the link reference definitions `[label]: dest` used by `[text][label]`,
+/// and the `src`, `href` and `data` attributes of html elements such as `<img>`, `<a>` and `<object>`.
+pub fn references(content: &str) -> Result<Vec<Reference>, UnlocatedLink> {
+    let parser = Parser::new_ext(content, Options::all()).into_offset_iter();
+    let mut references = vec![];
+    // the links which are not closed yet, with the end of their text so far
+    let mut open_links: Vec<usize> = vec![];
+    // the destinations of the links which refer to a definition
+    let mut defined_dests: Vec<String> = vec![];
+    // the text, which can not contain a link reference definition
+    let mut text_ranges: Vec<Range<usize>> = vec![];
+    for (event, range) in parser {
+        // the end of a link has the same range as its start, which would cover its destination
+        let is_link_end = matches!(
+            event,
+            Event::End(Tag::Link(..)) | Event::End(Tag::Image(..))
+        );
+        if !is_link_end {
+            for text_end in open_links.iter_mut() {
+                *text_end = (*text_end).max(range.end);
+            }
+        }
+        match event {
+            Event::Start(Tag::Link(..)) | Event::Start(Tag::Image(..)) => {
+                // the text comes after `[` or `![`
+                let marker = if content[range.clone()].starts_with('!') {
+                    2
+                } else {
+                    1
+                };
+                open_links.push(range.start + marker);
+            }
+            Event::End(Tag::Link(link_type, dest, _))
+            | Event::End(Tag::Image(link_type, dest, _)) => {
+                let text_end = open_links.pop().unwrap_or(range.start);
+                let dest = dest.to_string();
+                match link_type {
+                    LinkType::Inline if dest.is_empty() => (),
+                    LinkType::Inline => {
+                        let dest_range = inline_destination(content, text_end..range.end)
+                            .filter(|dest_range| unescape(&content[dest_range.clone()]) == dest)
+                            .ok_or_else(|| UnlocatedLink(dest.clone()))?;
+                        references.push(Reference {
+                            dest,
+                            range: dest_range,
+                        });
+                    }
+                    // `<dest>`
+                    LinkType::Autolink => references.push(Reference {
+                        dest,
+                        range: range.start + 1..range.end - 1,
+                    }),
+                    LinkType::Email => (),
+                    _ => defined_dests.push(dest),
+                }
+            }
+            Event::Text(_) | Event::Code(_) => text_ranges.push(range),
+            Event::Html(_) => {
+                references.extend(html_references(&content[range.clone()], range.start));
+                text_ranges.push(range);
+            }
+            _ => (),
+        }
+    }
+    let definitions = definitions(content, &text_ranges);
+    for dest in defined_dests.iter() {
+        if !definitions
+            .iter()
+            .any(|definition| definition.dest == *dest)
+        {
+            return Err(UnlocatedLink(dest.to_string()));
+        }
+    }
+    // only the definitions which are used are rendered
+    references.extend(
+        definitions
+            .into_iter()
+            .filter(|definition| defined_dests.contains(&definition.dest)),
+    );
+    // images nested in a link comes after the link
+    references.sort_by_key(|reference| reference.range.start);
+    Ok(references)
+}
+
+/// The range of the destination in `](dest "title")`, excluding the `<` and `>` around it.
+/// The `link` range starts at the end of the text of the link.
+fn inline_destination(content: &str, link: Range<usize>) -> Option<Range<usize>> {
+    let after_text = link.start + content[link.clone()].find("](")? + 2;
+    destination(content, after_text..link.end)
+}
+
+/// The range of the destination which is the first thing in this range after the whitespaces.
+/// It is either enclosed in `<` and `>`, or ends at a whitespace or an unbalanced `)`.
+fn destination(content: &str, range: Range<usize>) -> Option<Range<usize>> {
+    let text = &content[range.clone()];
+    let start = range.start + (text.len() - text.trim_start().len());
+    let text = &content[start..range.end];
+    if let Some(enclosed) = text.strip_prefix('<') {
+        let len = find_unescaped(enclosed, |c| c == '>' || c == '\n')?;
+        if enclosed[len..].starts_with('>') {
+            return Some(start + 1..start + 1 + len);
+        }
+        return None;
+    }
+    let mut depth = 0;
+    let mut is_escaped = false;
+    for (index, c) in text.char_indices() {
+        if is_escaped {
+            is_escaped = false;
+            continue;
+        }
+        match c {
+            '\\' => is_escaped = true,
+            '(' => depth += 1,
+            ')' if depth == 0 => return Some(start..start + index),
+            ')' => depth -= 1,
+            c if c.is_whitespace() => return Some(start..start + index),
+            _ => (),
+        }
+    }
+    Some(start..range.end)
+}
+
+/// the index of the first character which is not escaped by a backslash and matches
+fn find_unescaped<F>(text: &str, matches: F) -> Option<usize>
+where
+    F: Fn(char) -> bool,
+{
+    let mut is_escaped = false;
+    for (index, c) in text.char_indices() {
+        if is_escaped {
+            is_escaped = false;
+        } else if c == '\\' {
+            is_escaped = true;
+        } else if matches(c) {
+            return Some(index);
+        }
+    }
+    None
+}
+
+/// The link reference definitions `[label]: dest`, which can be indented or in a blockquote.
+/// The ones in `text_ranges` such as inside a code block or a paragraph are not definitions.
+fn definitions(content: &str, text_ranges: &[Range<usize>]) -> Vec<Reference> {
+    let line_starts =
+        std::iter::once(0).chain(content.match_indices('\n').map(|(index, _)| index + 1));
+    let mut definitions = vec![];
+    for line_start in line_starts {
+        let line = &content[line_start..];
+        let label_start =
+            line_start + (line.len() - line.trim_start_matches([' ', '\t', '>']).len());
+        if text_ranges.iter().any(|range| range.contains(&label_start)) {
+            continue;
+        }
+        let label = &content[label_start..];
+        // footnote definitions don't have a destination
+        if !label.starts_with('[') || label.starts_with("[^") {
+            continue;
+        }
+        let label_end = match find_unescaped(&label[1..], |c| c == ']' || c == '[') {
+            Some(len) if label[1 + len..].starts_with("]:") => label_start + 1 + len,
+            _ => continue,
+        };
+        let dest_range = match destination(content, label_end + 2..content.len()) {
+            Some(dest_range) if !dest_range.is_empty() => dest_range,
+            _ => continue,
+        };
+        definitions.push(Reference {
+            dest: unescape(&content[dest_range.clone()]),
+            range: dest_range,
+        });
+    }
+    definitions
+}
+
+/// resolve the backslash escapes and the character references, the same as the markdown parser
+fn unescape(dest: &str) -> String {
+    let mut unescaped = String::with_capacity(dest.len());
+    let mut rest = dest;
+    while let Some(c) = rest.chars().next() {
+        let next = rest[c.len_utf8()..].chars().next();
+        match (c, next) {
+            ('\\', Some(next)) if next.is_ascii_punctuation() => {
+                unescaped.push(next);
+                rest = &rest[1 + next.len_utf8()..];
+            }
+            ('&', _) => match character_reference(rest) {
+                Some((decoded, len)) => {
+                    unescaped.push(decoded);
+                    rest = &rest[len..];
+                }
+                None => {
+                    unescaped.push('&');
+                    rest = &rest[1..];
+                }
+            },
+            _ => {
+                unescaped.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+    unescaped
+}
+
+/// decode `&amp;`, `&#35;` or `&#x23;` at the start of the text, with the length it takes
+fn character_reference(text: &str) -> Option<(char, usize)> {
+    let end = text.find(';')?;
+    let name = &text[1..end];
+    let decoded = match name {
+        "amp" => '&',
+        "lt" => '<',
+        "gt" => '>',
+        "quot" => '"',
+        "apos" => '\'',
+        _ => {
+            let code = match name.strip_prefix('#') {
+                Some(hex) if hex.starts_with('x') || hex.starts_with('X') => {
+                    u32::from_str_radix(&hex[1..], 16).ok()?
+                }
+                Some(decimal) => decimal.parse().ok()?,
+                None => return None,
+            };
+            std::char::from_u32(code)?
+        }
+    };
+    Some((decoded, end + 1))
+}
+
+/// the quoted values of the html attributes which refers to other files,
+/// `offset` is the location of the html in the markdown
+fn html_references(html: &str, offset: usize) -> Vec<Reference> {
+    let mut references = vec![];
+    for attribute in HTML_ATTRIBUTES.iter() {
+        for (index, _) in html.match_indices(attribute) {
+            // the attribute must not be part of a longer name such as `data-src=`
+            let is_start = html[..index]
+                .chars()
+                .last()
+                .map(|c| c.is_whitespace())
+                .unwrap_or(false);
+            let value = &html[index + attribute.len()..];
+            let quote = match value.chars().next() {
+                Some(quote) if is_start && (quote == '"' || quote == '\'') => quote,
+                _ => continue,
+            };
+            if let Some(len) = value[1..].find(quote) {
+                let start = offset + index + attribute.len() + 1;
+                references.push(Reference {
+                    dest: value[1..1 + len].to_string(),
+                    range: start..start + len,
+                });
+            }
+        }
+    }
+    references
+}
+
+/// Replace the destination of links and images with the result of `rewrite`,
+/// the link is left as is when `rewrite` returns None
+pub fn rewrite_links<F>(content: &str, mut rewrite: F) -> Result<String, UnlocatedLink>
+where
+    F: FnMut(&str) -> Option<String>,
+{
+    let mut rewritten = String::with_capacity(content.len());
+    let mut last = 0;
+    for reference in references(content)? {
+        if let Some(new_dest) = rewrite(&reference.dest) {
+            rewritten.push_str(&content[last..reference.range.start]);
+            rewritten.push_str(&new_dest);
+            last = reference.range.end;
+        }
+    }
+    rewritten.push_str(&content[last..]);
+    Ok(rewritten)
+}
+
+/// whether the destination refers to a file relative to the note
+pub fn is_local(dest: &str) -> bool {
+    !(dest.starts_with('#')
+        || dest.starts_with('/')
+        || dest.starts_with("mailto:")
+        || dest.starts_with("data:")
+        || dest.contains("://"))
+}
+
+/// split the destination into the path and the `#fragment` or `?query` part
+pub fn split_fragment(dest: &str) -> (&str, &str) {
+    match dest.find(|c| c == '#' || c == '?') {
+        Some(index) => dest.split_at(index),
+        None => (dest, ""),
+    }
+}
+
+/// Join the path to the directory it is relative to, resolving the `.` and `..` in it.
+/// The result does not go above the root, same as the browsers do with urls.
+pub fn join_relative(dir: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = dir.split('/').filter(|part| !part.is_empty()).collect();
+    for part in path.split('/') {
+        match part {
+            "" | "." => (),
+            ".." => {
+                parts.pop();
+            }
+            part => parts.push(part),
+        }
+    }
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dests(content: &str) -> Vec<(String, &str)> {
+        references(content)
+            .expect("located")
+            .into_iter()
+            .map(|reference| (reference.dest, &content[reference.range]))
+            .collect()
+    }
+
+    #[test]
+    fn title_containing_the_destination() {
+        let content = r#"[a](b.png "b.png")"#;
+        let references = references(content).expect("located");
+        assert_eq!(references.len(), 1);
+        assert_eq!(references[0].range, 4..9);
+    }
+
+    #[test]
+    fn enclosed_and_escaped_destinations() {
+        assert_eq!(
+            dests(r"![x](<a b.png>) [y](c\_d.png)"),
+            vec![
+                ("a b.png".to_string(), "a b.png"),
+                ("c_d.png".to_string(), r"c\_d.png")
+            ]
+        );
+    }
+
+    #[test]
+    fn reference_links_use_their_definition() {
+        let content = "![x][ref]\n\n[ref]: ../../secret.png\n[unused]: other.png\n";
+        assert_eq!(
+            dests(content),
+            vec![("../../secret.png".to_string(), "../../secret.png")]
+        );
+    }
+
+    #[test]
+    fn rewrite_reference_definition() {
+        let content = "[x][ref]\n\n[ref]: a.png \"a.png\"\n";
+        let rewritten =
+            rewrite_links(content, |dest| Some(format!("/ipfs/{}", dest))).expect("located");
+        assert_eq!(rewritten, "[x][ref]\n\n[ref]: /ipfs/a.png \"a.png\"\n");
+    }
+
+    #[test]
+    fn join_to_the_note_directory() {
+        assert_eq!(join_relative("sub", "img/plot.png"), "sub/img/plot.png");
+        assert_eq!(join_relative("sub/dir", "../plot.png"), "sub/plot.png");
+        assert_eq!(join_relative("", "../../plot.png"), "plot.png");
+    }
+
+    #[test]
+    fn nested_image_in_a_link() {
+        assert_eq!(
+            dests("[![img](a.png)](b.md)"),
+            vec![("a.png".to_string(), "a.png"), ("b.md".to_string(), "b.md")]
+        );
+    }
+}
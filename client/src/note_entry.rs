@@ -0,0 +1,10 @@
+use serde::{Deserialize, Serialize};
+
+/// An entry in the listing of notes
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct NoteEntry {
+    /// the path of the note relative to the workspace root, separated with `/`
+    pub path: String,
+    /// the size of the note in bytes
+    pub size: u64,
+}
@@ -0,0 +1,20 @@
+use serde::{Deserialize, Serialize};
+
+/// Notification sent by the server when a note in the workspace is modified on disk
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind")]
+pub enum NoteEvent {
+    /// the note was created or its content has changed
+    Changed { path: String, content: String },
+    /// the note was deleted or moved away
+    Removed { path: String },
+}
+
+impl NoteEvent {
+    pub fn path(&self) -> &str {
+        match self {
+            NoteEvent::Changed { path, .. } => path,
+            NoteEvent::Removed { path } => path,
+        }
+    }
+}
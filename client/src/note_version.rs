@@ -0,0 +1,58 @@
+use serde::{Deserialize, Serialize};
+
+/// the response header which contains the modification time of the note,
+/// the hash of the content is in the `ETag` header
+pub const MODIFIED_HEADER: &str = "X-Modified";
+
+/// The version of a note on disk, which is used to detect the changes made by other programs
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct NoteVersion {
+    /// the modification time of the file in milliseconds since the unix epoch
+    pub modified: u64,
+    /// the hash of the content, in hex
+    pub hash: String,
+}
+
+impl NoteVersion {
+    /// The content of the file has changed since this version was read.
+    /// Only the hash is compared, since the content can change within the resolution of the
+    /// modification time, while a file which is only touched keeps its hash.
+    pub fn is_modified(&self, current: &NoteVersion) -> bool {
+        self.hash != current.hash
+    }
+
+    /// the value of the `ETag` header
+    pub fn etag(&self) -> String {
+        format!("\"{}\"", self.hash)
+    }
+
+    /// the version from the `ETag` and the `X-Modified` headers
+    pub fn from_headers(etag: &str, modified: &str) -> Option<Self> {
+        Some(Self {
+            modified: modified.trim().parse().ok()?,
+            hash: etag.trim().trim_matches('"').to_string(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn version(modified: u64, hash: &str) -> NoteVersion {
+        NoteVersion {
+            modified,
+            hash: hash.to_string(),
+        }
+    }
+
+    #[test]
+    fn only_the_hash_is_compared() {
+        let loaded = version(1000, "aaaa");
+        // written again within the same millisecond
+        assert!(loaded.is_modified(&version(1000, "bbbb")));
+        assert!(loaded.is_modified(&version(2000, "bbbb")));
+        // only touched
+        assert!(!loaded.is_modified(&version(2000, "aaaa")));
+    }
+}
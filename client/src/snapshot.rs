@@ -0,0 +1,14 @@
+use serde::{Deserialize, Serialize};
+
+/// An unsaved buffer which is kept in the recovery directory, so it can be restored when the
+/// app was closed or has crashed before it was saved
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Snapshot {
+    /// the session of the app which took the snapshot, a session only replaces its own snapshots
+    pub session: String,
+    /// the note of the buffer, None if it was not saved to a file yet
+    pub note: Option<String>,
+    pub content: String,
+    /// the unix time in seconds when the snapshot was taken
+    pub saved_at: u64,
+}
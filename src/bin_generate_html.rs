@@ -1,9 +1,85 @@
+mod config;
+mod export;
+mod ipfs;
+mod publish;
+mod recent;
+mod recovery;
 mod serve_files;
 mod server;
+mod version;
+mod watcher;
+mod workspace;
 use sauron::prelude::*;
+use std::fs;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+/// Generate html page, when a markdown file is given the page is rendered with its content.
+/// When a directory is given, all of its notes are exported into a static site.
+#[derive(StructOpt, Debug)]
+#[structopt(name = "generate_html")]
+struct Opt {
+    /// the markdown note to render into a static html page,
+    /// or the workspace directory to export into a static site
+    #[structopt(parse(from_os_str))]
+    input: Option<PathBuf>,
+
+    /// the directory where the static site is written, required when exporting a workspace
+    #[structopt(long, short, parse(from_os_str))]
+    out_dir: Option<PathBuf>,
+
+    /// the title of the page, defaults to the file name of the note
+    #[structopt(long)]
+    title: Option<String>,
+}
 
 fn main() {
-    let settings = client::Settings::default();
-    let html = server::page::index(&settings).render_to_string();
+    let opt = Opt::from_args();
+    if let Some(input) = opt.input.as_ref().filter(|input| input.is_dir()) {
+        export_site(input, opt.out_dir.as_deref());
+        return;
+    }
+    let html = match &opt.input {
+        Some(input) => {
+            let content = match fs::read_to_string(input) {
+                Ok(content) => content,
+                Err(e) => {
+                    eprintln!("unable to read {}: {}", input.display(), e);
+                    std::process::exit(1);
+                }
+            };
+            let title = opt.title.clone().unwrap_or_else(|| {
+                input
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default()
+            });
+            server::page::static_page(&title, client::render_markdown(&content)).render_to_string()
+        }
+        None => {
+            let settings = client::Settings::default();
+            server::page::index(&settings).render_to_string()
+        }
+    };
     println!("{}", html);
 }
+
+fn export_site(input: &Path, out_dir: Option<&Path>) {
+    let out_dir = match out_dir {
+        Some(out_dir) => out_dir,
+        None => {
+            eprintln!("--out-dir is required when exporting a workspace");
+            std::process::exit(1);
+        }
+    };
+    let result = workspace::Workspace::new(input)
+        .map_err(server::ServiceError::from)
+        .and_then(|workspace| export::export_site(&workspace, out_dir));
+    match result {
+        Ok(pages) => eprintln!("exported {} notes into {}", pages, out_dir.display()),
+        Err(e) => {
+            eprintln!("unable to export {}: {}", input.display(), e);
+            std::process::exit(1);
+        }
+    }
+}
@@ -0,0 +1,75 @@
+// only the signing of the license is used here
+#[allow(unused)]
+mod config;
+#[allow(unused)]
+mod license;
+use ed25519_dalek::{Keypair, PublicKey, SecretKey};
+use license::License;
+use std::fs;
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+/// Issue licenses of the desktop app, this is meant for testing.
+/// Build the desktop app with `MADNOTES_LICENSE_PUBLIC_KEY` set to the generated public key
+/// so it accepts the licenses signed with the matching secret key.
+#[derive(StructOpt, Debug)]
+#[structopt(name = "issue_license")]
+enum Opt {
+    /// create a new key pair, the secret key is written to the file
+    GenerateKey {
+        /// where to write the base64 of the secret key
+        #[structopt(parse(from_os_str))]
+        secret_key: PathBuf,
+    },
+    /// sign a license with the secret key and print it
+    Sign {
+        /// the file containing the base64 of the secret key
+        #[structopt(long, parse(from_os_str))]
+        secret_key: PathBuf,
+        /// the name of the licensee
+        #[structopt(long)]
+        licensee: String,
+        /// the number of days the license is valid, it never expires if not specified
+        #[structopt(long)]
+        days: Option<u64>,
+    },
+}
+
+fn main() {
+    match Opt::from_args() {
+        Opt::GenerateKey { secret_key } => {
+            let keypair = Keypair::generate(&mut rand::rngs::OsRng);
+            if let Err(e) = fs::write(&secret_key, base64::encode(keypair.secret.as_bytes())) {
+                eprintln!("unable to write {}: {}", secret_key.display(), e);
+                std::process::exit(1);
+            }
+            println!("{}", base64::encode(keypair.public.as_bytes()));
+        }
+        Opt::Sign {
+            secret_key,
+            licensee,
+            days,
+        } => {
+            let keypair = match read_keypair(&secret_key) {
+                Ok(keypair) => keypair,
+                Err(e) => {
+                    eprintln!("invalid secret key {}: {}", secret_key.display(), e);
+                    std::process::exit(1);
+                }
+            };
+            let expires_at = days.map(|days| license::now() + days * 24 * 60 * 60);
+            println!(
+                "{}",
+                License::issue(&keypair, &licensee, expires_at).to_json()
+            );
+        }
+    }
+}
+
+fn read_keypair(file: &Path) -> Result<Keypair, String> {
+    let encoded = fs::read_to_string(file).map_err(|e| e.to_string())?;
+    let bytes = base64::decode(encoded.trim()).map_err(|e| e.to_string())?;
+    let secret = SecretKey::from_bytes(&bytes).map_err(|e| e.to_string())?;
+    let public = PublicKey::from(&secret);
+    Ok(Keypair { secret, public })
+}
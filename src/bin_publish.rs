@@ -0,0 +1,53 @@
+mod config;
+mod ipfs;
+mod publish;
+mod recent;
+mod recovery;
+mod serve_files;
+mod server;
+mod version;
+mod watcher;
+mod workspace;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Publish a note together with the local files it refers to into the blockstore of the workspace.
+/// The cid of the published note is printed, which can be viewed at `/ipfs/<cid>` in the server.
+#[derive(StructOpt, Debug)]
+#[structopt(name = "publish")]
+struct Opt {
+    /// the directory where the notes are stored, defaults to the current directory
+    #[structopt(long, parse(from_os_str))]
+    workspace: Option<PathBuf>,
+
+    /// the note to publish, relative to the workspace
+    note: String,
+}
+
+fn main() {
+    let opt = Opt::from_args();
+    let workspace = match &opt.workspace {
+        Some(dir) => workspace::Workspace::new(dir),
+        None => workspace::Workspace::current_dir(),
+    };
+    let workspace = match workspace {
+        Ok(workspace) => workspace,
+        Err(e) => {
+            eprintln!("invalid workspace: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let blockstore = ipfs::Blockstore::for_workspace(&workspace);
+    match publish::publish(&workspace, &blockstore, &opt.note) {
+        Ok(published) => {
+            for file in published.files.iter() {
+                eprintln!("{} {}", file.cid, file.path);
+            }
+            println!("{}", published.cid);
+        }
+        Err(e) => {
+            eprintln!("unable to publish {}: {}", opt.note, e);
+            std::process::exit(1);
+        }
+    }
+}
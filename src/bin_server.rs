@@ -1,15 +1,87 @@
 #![deny(warnings)]
+use std::net::IpAddr;
+use std::path::PathBuf;
+use structopt::StructOpt;
 use tokio::sync::oneshot;
 
+mod config;
+mod ipfs;
+mod publish;
+mod recent;
+mod recovery;
 mod serve_files;
 mod server;
+mod version;
+mod watcher;
+mod workspace;
+
+/// Serve the notes of a workspace over http
+#[derive(StructOpt, Debug)]
+#[structopt(name = "server")]
+struct Opt {
+    /// the directory where the notes are stored, defaults to the current directory
+    #[structopt(long, parse(from_os_str))]
+    workspace: Option<PathBuf>,
+
+    /// the port to listen to
+    #[structopt(long, default_value = "3031")]
+    port: u16,
+
+    /// the ip address to bind to, use 0.0.0.0 to listen on all interfaces
+    #[structopt(long, default_value = "127.0.0.1")]
+    bind: IpAddr,
+
+    /// the note to open at startup, relative to the workspace
+    #[structopt(long)]
+    open: Option<String>,
+
+    /// the title of the page
+    #[structopt(long)]
+    title: Option<String>,
+}
 
 #[tokio::main]
 async fn main() {
-    let settings = client::Settings::default();
+    let opt = Opt::from_args();
+
+    let workspace = match &opt.workspace {
+        Some(dir) => workspace::Workspace::new(dir),
+        None => workspace::Workspace::current_dir(),
+    };
+    let workspace = match workspace {
+        Ok(workspace) => workspace,
+        Err(e) => {
+            eprintln!("invalid workspace: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(note) = &opt.open {
+        if let Err(e) = workspace.read_note(note) {
+            eprintln!("unable to open {}: {}", note, e);
+            std::process::exit(1);
+        }
+    }
+
+    let mut settings = client::Settings::default();
+    if let Some(title) = opt.title {
+        settings.app_title = title;
+    }
+    settings.open_note = opt.open;
 
     let (socket_tx, _socket_rx) = oneshot::channel();
     let (_shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
 
-    server::serve(settings, None, Some(3031), socket_tx, shutdown_rx).await;
+    let result = server::serve(
+        server::State::new(settings, workspace),
+        Some(opt.bind),
+        Some(opt.port),
+        socket_tx,
+        shutdown_rx,
+    )
+    .await;
+    if let Err(e) = result {
+        eprintln!("unable to serve on {}:{}: {}", opt.bind, opt.port, e);
+        std::process::exit(1);
+    }
 }
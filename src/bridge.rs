@@ -0,0 +1,65 @@
+//! Serve the requests of the webview through `invoke` when the desktop app does not open a port.
+//!
+//! The requests are handled by the same service as the server, so every endpoint works the
+//! same in both builds.
+//! The note events are not available, since they need a long lived connection.
+use crate::route::Route;
+use crate::serve_files::RawResponse;
+use crate::server::service::{self, ServiceRequest};
+use crate::server::{ServiceError, State};
+use serde::Serialize;
+
+/// The response passed back to the webview as json
+#[derive(Debug, Serialize)]
+pub(crate) struct BridgeResponse {
+    pub(crate) status: u16,
+    /// a list instead of a map, since a header can be repeated
+    pub(crate) headers: Vec<(String, String)>,
+    /// the base64 of the response body
+    pub(crate) body: String,
+}
+
+impl BridgeResponse {
+    fn from_raw(raw_response: RawResponse) -> Result<Self, ServiceError> {
+        Ok(Self {
+            status: raw_response.status.as_u16(),
+            headers: raw_response
+                .headers
+                .into_iter()
+                .map(|(name, value)| (name.to_string(), value))
+                .collect(),
+            body: base64::encode(&raw_response.content.into_bytes()?),
+        })
+    }
+
+    fn from_error(e: ServiceError) -> Self {
+        Self {
+            status: e.status_code().as_u16(),
+            headers: vec![("Content-Type".to_string(), "application/json".to_string())],
+            body: base64::encode(e.to_json()),
+        }
+    }
+}
+
+pub(crate) fn serve(state: &State, route: &Route) -> BridgeResponse {
+    match dispatch(state, route).and_then(BridgeResponse::from_raw) {
+        Ok(response) => response,
+        Err(e) => {
+            eprintln!("error serving {} {}: {}", route.method, route.url, e);
+            BridgeResponse::from_error(e)
+        }
+    }
+}
+
+/// the route is turned into the same request the server passes to the service
+fn dispatch<'a>(state: &State, route: &Route) -> Result<RawResponse<'a>, ServiceError> {
+    let (path, query) = route.path_and_query()?;
+    let request = ServiceRequest {
+        method: route.method()?,
+        path,
+        query,
+        headers: route.headers()?,
+        body: route.body()?,
+    };
+    service::handle(state, &request)
+}
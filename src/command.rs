@@ -0,0 +1,339 @@
+//! Execute the commands sent by the client through `invoke` in the desktop app
+use crate::license::{self, License, LicenseError};
+use crate::recent::RecentFiles;
+use crate::server::ServiceError;
+use crate::server::State;
+use crate::version;
+use client::{Command, CommandError, CommandOutput, CommandResult, LicenseInfo, NoteVersion};
+use native_dialog::{FileDialog, MessageDialog, MessageType};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use web_view::WebView;
+
+/// The state of the desktop app, this is the user data of the webview
+pub(crate) struct Desktop {
+    /// the title of the app, which is appended to the title of the window
+    app_title: String,
+    /// the state shared with the server, the requests of the webview are served with it
+    pub(crate) state: State,
+    /// the files recently opened or saved
+    recent: RecentFiles,
+    /// the files the user picked in a dialog or on the command line, or opened in this session
+    picked: HashSet<PathBuf>,
+    /// the verified license, None if the app is not registered
+    license: Option<License>,
+    /// the changes reported by the webview which are not saved yet
+    unsaved: Option<Unsaved>,
+}
+
+/// The unsaved content of the buffer, together with its note and the version it was loaded at
+struct Unsaved {
+    session: String,
+    note: Option<String>,
+    content: String,
+    version: Option<NoteVersion>,
+}
+
+impl Desktop {
+    /// this also loads the installed license
+    pub(crate) fn new(app_title: String, state: State) -> Self {
+        let license = match license::load() {
+            Ok(license) => Some(license),
+            Err(LicenseError::Missing) => None,
+            Err(e) => {
+                eprintln!("the installed license is not used: {}", e);
+                None
+            }
+        };
+        let picked = state
+            .settings
+            .open_note
+            .iter()
+            .map(|note| canonical(Path::new(note)))
+            .collect();
+        Self {
+            app_title,
+            state,
+            recent: RecentFiles::for_desktop(),
+            picked,
+            license,
+            unsaved: None,
+        }
+    }
+
+    /// the title of the app, which is marked when the app is not registered
+    pub(crate) fn app_title(&self) -> String {
+        if self.license.is_some() {
+            self.app_title.clone()
+        } else {
+            format!("{} (UNREGISTERED)", self.app_title)
+        }
+    }
+
+    fn open_file(&mut self) -> CommandResult {
+        let path = FileDialog::new()
+            .set_location(self.state.workspace.root())
+            .add_filter("Markdown", &["md", "markdown"])
+            .show_open_single_file()
+            .map_err(dialog_error)?;
+        match path {
+            Some(path) => {
+                self.picked.insert(canonical(&path));
+                self.open_path(&path)
+            }
+            None => Ok(CommandOutput::Cancelled),
+        }
+    }
+
+    fn open_path(&mut self, path: &Path) -> CommandResult {
+        self.check_allowed(path)?;
+        let content = fs::read_to_string(path).map_err(ServiceError::from)?;
+        let version = version::note_version(path).map_err(ServiceError::from)?;
+        // the opened file stays allowed, even if it is removed from the recent files
+        self.picked.insert(canonical(path));
+        let path = path.display().to_string();
+        self.recent.add(&path);
+        Ok(CommandOutput::Opened {
+            path,
+            content,
+            version,
+        })
+    }
+
+    /// the files which no longer exist are pruned from the list
+    fn list_recent(&mut self) -> CommandResult {
+        Ok(CommandOutput::Recent {
+            paths: self.recent.list(|path| Path::new(path).is_file()),
+        })
+    }
+
+    /// The save dialog is shown when there is no path.
+    /// The file is not written if it was modified since the expected version.
+    fn save_file(
+        &mut self,
+        path: Option<String>,
+        content: String,
+        expected: Option<NoteVersion>,
+    ) -> CommandResult {
+        let path = match path {
+            Some(path) => {
+                let path = Path::new(&path).to_path_buf();
+                self.check_allowed(&path)?;
+                path
+            }
+            None => {
+                let path = FileDialog::new()
+                    .set_location(self.state.workspace.root())
+                    .add_filter("Markdown", &["md", "markdown"])
+                    .show_save_single_file()
+                    .map_err(dialog_error)?;
+                match path {
+                    Some(path) => {
+                        self.picked.insert(canonical(&path));
+                        path
+                    }
+                    None => return Ok(CommandOutput::Cancelled),
+                }
+            }
+        };
+        let is_written = version::write_if_unmodified(&path, content.as_bytes(), expected.as_ref())
+            .map_err(ServiceError::from)?;
+        if !is_written {
+            return Err(ServiceError::PreconditionFailed(path.display().to_string()).into());
+        }
+        let version = version::note_version(&path).map_err(ServiceError::from)?;
+        let path = path.display().to_string();
+        self.recent.add(&path);
+        Ok(CommandOutput::Saved { path, version })
+    }
+
+    /// The window of the webview is closed without any `beforeunload` event, so the unsaved
+    /// changes are offered to be saved once it is closed.
+    /// They are kept as a snapshot when they are not saved, and recovered on the next launch.
+    pub(crate) fn save_on_close(&mut self) {
+        let unsaved = match self.unsaved.take() {
+            Some(unsaved) => unsaved,
+            None => return,
+        };
+        let name = unsaved
+            .note
+            .as_deref()
+            .and_then(|note| Path::new(note).file_name())
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_else(|| "Untitled".to_string());
+        let answer = MessageDialog::new()
+            .set_type(MessageType::Warning)
+            .set_title(&self.app_title)
+            .set_text(&format!(
+                "{} has unsaved changes. Save them before closing?",
+                name
+            ))
+            .show_confirm();
+        let is_done = match answer {
+            Ok(true) => {
+                let saved = self.save_file(
+                    unsaved.note.clone(),
+                    unsaved.content.clone(),
+                    unsaved.version.clone(),
+                );
+                match saved {
+                    Ok(CommandOutput::Saved { .. }) => true,
+                    Ok(_) => false,
+                    Err(e) => {
+                        eprintln!("unable to save {}: {}", name, e.message);
+                        false
+                    }
+                }
+            }
+            // the changes are discarded
+            Ok(false) => true,
+            Err(e) => {
+                eprintln!("unable to ask to save {}: {}", name, e);
+                false
+            }
+        };
+        let recovery = &self.state.recovery;
+        let result = if is_done {
+            recovery.remove(&unsaved.session, unsaved.note.as_deref())
+        } else {
+            recovery.store(&unsaved.session, unsaved.note.as_deref(), &unsaved.content)
+        };
+        if let Err(e) = result {
+            eprintln!("unable to update the snapshot of {}: {}", name, e);
+        }
+    }
+
+    fn read_workspace_file(&self, path: String) -> CommandResult {
+        let file = self.state.workspace.resolve(&path)?;
+        read_file(path, &file)
+    }
+
+    /// The webview only gets to the files in the workspace, the ones the user picked, the recent
+    /// files and the notes of the unsaved snapshots, so a script in a note can't read or
+    /// overwrite any other file of the user.
+    fn check_allowed(&mut self, path: &Path) -> Result<(), ServiceError> {
+        let file = canonical(path);
+        // a file which doesn't exist can't be canonical, so it must be listed as is
+        let in_workspace = path.exists() && file.starts_with(self.state.workspace.root());
+        if in_workspace || self.picked.contains(&file) {
+            return Ok(());
+        }
+        let snapshots = self.state.recovery.list()?;
+        let is_listed = self
+            .recent
+            .list(|_| true)
+            .into_iter()
+            .chain(snapshots.into_iter().filter_map(|snapshot| snapshot.note))
+            .any(|listed| canonical(Path::new(&listed)) == file);
+        if is_listed {
+            Ok(())
+        } else {
+            Err(ServiceError::Forbidden(path.display().to_string()))
+        }
+    }
+
+    fn window_title(&self, title: &str) -> String {
+        format!("{} - {}", title, self.app_title())
+    }
+
+    fn license_info(&self) -> Option<LicenseInfo> {
+        self.license.as_ref().map(|license| LicenseInfo {
+            licensee: license.licensee.clone(),
+            expires_at: license.expires_at,
+        })
+    }
+
+    fn install_license(&mut self, license: &str) -> CommandResult {
+        let license =
+            license::install(license).map_err(|e| CommandError::new("invalid_license", e))?;
+        self.license = Some(license);
+        Ok(CommandOutput::License {
+            license: self.license_info(),
+        })
+    }
+}
+
+/// Execute the command, the webview is needed by the commands which change the window
+pub(crate) fn execute(webview: &mut WebView<'_, Desktop>, command: Command) -> CommandResult {
+    match command {
+        Command::OpenFile => webview.user_data_mut().open_file(),
+        Command::SaveFile {
+            path,
+            content,
+            version,
+        } => webview.user_data_mut().save_file(path, content, version),
+        Command::OpenPath { path } => webview.user_data_mut().open_path(Path::new(&path)),
+        Command::ListRecent => webview.user_data_mut().list_recent(),
+        Command::ClearRecent => {
+            webview.user_data_mut().recent.clear();
+            Ok(CommandOutput::Recent { paths: vec![] })
+        }
+        Command::ReadWorkspaceFile { path } => webview.user_data().read_workspace_file(path),
+        Command::ReadFile { path } => {
+            let file = PathBuf::from(&path);
+            webview.user_data_mut().check_allowed(&file)?;
+            read_file(path, &file)
+        }
+        Command::ShowMessage { title, message } => {
+            MessageDialog::new()
+                .set_type(MessageType::Info)
+                .set_title(&title)
+                .set_text(&message)
+                .show_alert()
+                .map_err(dialog_error)?;
+            Ok(CommandOutput::Done)
+        }
+        Command::SetTitle { title } => {
+            let title = webview.user_data().window_title(&title);
+            webview
+                .set_title(&title)
+                .map_err(|e| CommandError::new("internal", e))?;
+            Ok(CommandOutput::Done)
+        }
+        Command::LicenseStatus => Ok(CommandOutput::License {
+            license: webview.user_data().license_info(),
+        }),
+        Command::InstallLicense { license } => webview.user_data_mut().install_license(&license),
+        Command::Unsaved {
+            session,
+            note,
+            content,
+            version,
+        } => {
+            webview.user_data_mut().unsaved = content.map(|content| Unsaved {
+                session,
+                note,
+                content,
+                version,
+            });
+            Ok(CommandOutput::Done)
+        }
+    }
+}
+
+impl From<ServiceError> for CommandError {
+    fn from(e: ServiceError) -> Self {
+        CommandError::new(e.kind(), &e)
+    }
+}
+
+/// the content of the file together with its version
+fn read_file(path: String, file: &Path) -> CommandResult {
+    let content = fs::read_to_string(file).map_err(ServiceError::from)?;
+    let version = version::note_version(file).map_err(ServiceError::from)?;
+    Ok(CommandOutput::FileContent {
+        path,
+        content,
+        version,
+    })
+}
+
+/// the path with its symlinks and `..` resolved, or as is when the file doesn't exist
+fn canonical(path: &Path) -> PathBuf {
+    path.canonicalize().unwrap_or_else(|_| path.to_path_buf())
+}
+
+fn dialog_error(e: native_dialog::Error) -> CommandError {
+    CommandError::new("internal", format!("dialog error: {}", e))
+}
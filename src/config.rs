@@ -0,0 +1,20 @@
+//! The files of madnotes in the user config directory
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+/// the directory of madnotes in the user config directory
+const CONFIG_DIR: &str = "madnotes";
+
+/// `~/.config/madnotes` on linux
+pub(crate) fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join(CONFIG_DIR))
+}
+
+/// a short and stable file name for the text, such as the root of a workspace
+pub(crate) fn file_key(text: &str) -> String {
+    Sha256::digest(text.as_bytes())
+        .iter()
+        .take(8)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
@@ -0,0 +1,234 @@
+//! Export the notes of a workspace into a static site which can be hosted anywhere
+use crate::serve_files;
+use crate::server::page;
+use crate::server::ServiceError;
+use crate::workspace::{self, Workspace};
+use client::links;
+use sauron::prelude::*;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// the font is copied here, relative to the output directory,
+/// which is where the css of the site pages refers to
+const FONT_PATH: &str = "assets/JuliaMono-Light.woff2";
+
+/// An entry in the navigation sidebar
+struct NavEntry {
+    /// the text of the link
+    title: String,
+    /// the path of the html page, relative to the root of the site
+    html_path: String,
+}
+
+/// Export every note of the workspace into an html page and copy the files they link to
+/// as is. Links between notes are rewritten to point to their html pages.
+/// Returns the number of pages written, excluding the index page.
+pub(crate) fn export_site(workspace: &Workspace, out_dir: &Path) -> Result<usize, ServiceError> {
+    fs::create_dir_all(out_dir)?;
+    let out_dir = out_dir.canonicalize()?;
+
+    let notes = workspace.list_notes()?;
+    let nav: Vec<NavEntry> = notes
+        .iter()
+        .map(|note| NavEntry {
+            title: note_title(&note.path),
+            html_path: html_path(&note.path),
+        })
+        .collect();
+
+    // the same file could be linked from more than one note
+    let mut linked_files: BTreeSet<String> = BTreeSet::new();
+    for (note, nav_entry) in notes.iter().zip(nav.iter()) {
+        let content = workspace.read_note(&note.path)?;
+        let unlocated =
+            |e: links::UnlocatedLink| ServiceError::BadRequest(format!("{}: {}", note.path, e));
+        for reference in links::references(&content).map_err(unlocated)? {
+            match workspace.linked_path(&note.path, &reference.dest) {
+                Ok(Some(relative)) => {
+                    linked_files.insert(relative);
+                }
+                Ok(None) => (),
+                Err(e) => eprintln!(
+                    "{} links to {} which is not copied: {}",
+                    note.path, reference.dest, e
+                ),
+            }
+        }
+        let content = links::rewrite_links(&content, rewrite_note_link).map_err(unlocated)?;
+        let root_prefix = root_prefix(&nav_entry.html_path);
+        let html = page::site_page(
+            &nav_entry.title,
+            &root_prefix,
+            sidebar(&nav, &root_prefix, Some(&nav_entry.html_path)),
+            client::render_markdown(&content),
+        )
+        .render_to_string();
+        write_file(&out_dir.join(&nav_entry.html_path), html.as_bytes())?;
+    }
+
+    let index = page::site_page(
+        &site_title(workspace),
+        "",
+        sidebar(&nav, "", None),
+        index_content(&nav),
+    )
+    .render_to_string();
+    write_file(&out_dir.join("index.html"), index.as_bytes())?;
+    write_file(&out_dir.join(FONT_PATH), serve_files::font_content())?;
+
+    for relative in linked_files {
+        let file = workspace.resolve(&relative)?;
+        // the notes are exported as pages, and the output directory could be inside the workspace
+        if workspace::is_note(&file)
+            || workspace::is_hidden(&relative)
+            || file.starts_with(&out_dir)
+        {
+            continue;
+        }
+        if !file.is_file() {
+            eprintln!("{} is linked but does not exist", relative);
+            continue;
+        }
+        let destination = out_dir.join(&relative);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&file, destination)?;
+    }
+    Ok(notes.len())
+}
+
+/// point links to other notes into their html pages
+fn rewrite_note_link(dest: &str) -> Option<String> {
+    if !links::is_local(dest) {
+        return None;
+    }
+    let (path, fragment) = links::split_fragment(dest);
+    if workspace::is_note(Path::new(path)) {
+        Some(format!("{}{}", html_path(path), fragment))
+    } else {
+        None
+    }
+}
+
+/// links to the index and to every note, the `current` page is highlighted
+fn sidebar(nav: &[NavEntry], root_prefix: &str, current: Option<&str>) -> Node<()> {
+    div(
+        [],
+        [
+            a(
+                [class("home"), href(format!("{}index.html", root_prefix))],
+                [text("Index")],
+            ),
+            ul(
+                [],
+                nav.iter()
+                    .map(|entry| {
+                        li(
+                            [],
+                            [a(
+                                [
+                                    classes_flag([(
+                                        "active",
+                                        current == Some(entry.html_path.as_str()),
+                                    )]),
+                                    href(format!("{}{}", root_prefix, entry.html_path)),
+                                ],
+                                [text(&entry.title)],
+                            )],
+                        )
+                    })
+                    .collect::<Vec<_>>(),
+            ),
+        ],
+    )
+}
+
+/// the listing of all the notes
+fn index_content(nav: &[NavEntry]) -> Node<()> {
+    ul(
+        [class("index")],
+        nav.iter()
+            .map(|entry| {
+                li(
+                    [],
+                    [a([href(entry.html_path.to_string())], [text(&entry.title)])],
+                )
+            })
+            .collect::<Vec<_>>(),
+    )
+}
+
+/// `notes/today.md` becomes `notes/today.html`
+fn html_path(note: &str) -> String {
+    PathBuf::from(note)
+        .with_extension("html")
+        .to_string_lossy()
+        .replace('\\', "/")
+}
+
+/// the relative path that goes back to the root of the site from this page
+fn root_prefix(html_path: &str) -> String {
+    "../".repeat(html_path.matches('/').count())
+}
+
+fn note_title(note: &str) -> String {
+    Path::new(note)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| note.to_string())
+}
+
+fn site_title(workspace: &Workspace) -> String {
+    workspace
+        .root()
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Notes".to_string())
+}
+
+fn write_file(path: &Path, content: &[u8]) -> Result<(), ServiceError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    Ok(fs::write(path, content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_linked_files_are_copied() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("notes");
+        fs::create_dir_all(root.join("sub")).unwrap();
+        fs::create_dir_all(root.join(".madnotes")).unwrap();
+        fs::write(
+            root.join("home.md"),
+            "[today](sub/today.md#top) ![plot](sub/my%20plot.png) [hidden](.madnotes/state)",
+        )
+        .unwrap();
+        fs::write(
+            root.join("sub/today.md"),
+            "![plot](my%20plot.png) [gone](gone.png)",
+        )
+        .unwrap();
+        fs::write(root.join("sub/my plot.png"), "plot").unwrap();
+        fs::write(root.join("unlinked.png"), "unlinked").unwrap();
+        fs::write(root.join(".madnotes/state"), "state").unwrap();
+        let workspace = Workspace::new(&root).unwrap();
+        let out_dir = dir.path().join("site");
+
+        assert_eq!(export_site(&workspace, &out_dir).unwrap(), 2);
+        let page = fs::read_to_string(out_dir.join("home.html")).unwrap();
+        assert!(page.contains("sub/today.html#top"));
+        assert!(!page.contains("today.md"));
+        assert!(out_dir.join("sub/today.html").is_file());
+        assert_eq!(fs::read(out_dir.join("sub/my plot.png")).unwrap(), b"plot");
+        assert!(!out_dir.join("unlinked.png").exists());
+        assert!(!out_dir.join(".madnotes").exists());
+        assert!(!out_dir.join("sub/today.md").exists());
+    }
+}
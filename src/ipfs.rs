@@ -0,0 +1,91 @@
+//! A local content-addressed blockstore which stands in for an IPFS node.
+//!
+//! The cids are compatible with the ones computed by `ipfs add --cid-version=1`,
+//! so the stored files can later be pinned to a real node with the same addresses.
+use crate::workspace::Workspace;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+pub(crate) use cid::{Cid, Codec, InvalidCid};
+
+mod cid;
+mod unixfs;
+
+/// the blocks are stored in this directory, relative to the workspace
+const BLOCKS_DIR: &str = ".madnotes/blocks";
+
+#[derive(Clone, Debug)]
+pub(crate) struct Blockstore {
+    dir: PathBuf,
+}
+
+impl Blockstore {
+    pub(crate) fn for_workspace(workspace: &Workspace) -> Self {
+        Self {
+            dir: workspace.root().join(BLOCKS_DIR),
+        }
+    }
+
+    /// store the file into blocks and return the cid of its root
+    pub(crate) fn add(&self, content: &[u8]) -> io::Result<Cid> {
+        let blocks = unixfs::chunk(content);
+        for block in blocks.iter() {
+            self.put(&block.cid, &block.data)?;
+        }
+        let root = blocks.last().expect("there is at least one block");
+        Ok(root.cid.clone())
+    }
+
+    /// read back the content of the file addressed by this cid
+    pub(crate) fn cat(&self, cid: &Cid) -> io::Result<Vec<u8>> {
+        let mut content = vec![];
+        self.cat_into(cid, &mut content)?;
+        Ok(content)
+    }
+
+    fn cat_into(&self, cid: &Cid, content: &mut Vec<u8>) -> io::Result<()> {
+        let block = self.get(cid)?;
+        match cid.codec() {
+            Codec::Raw => content.extend_from_slice(&block),
+            Codec::DagPb => {
+                let (links, inline_data) = unixfs::decode_node(&block).ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidData, format!("corrupted {}", cid))
+                })?;
+                content.extend_from_slice(&inline_data);
+                for link in links {
+                    self.cat_into(&link.cid, content)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub(crate) fn put(&self, cid: &Cid, block: &[u8]) -> io::Result<()> {
+        let path = self.block_path(cid);
+        // blocks are immutable, there is nothing to do if it is already stored
+        if path.exists() {
+            return Ok(());
+        }
+        fs::create_dir_all(&self.dir)?;
+        // write to a temporary file first, so a block is never partially written
+        let tmp_path = path.with_extension("tmp");
+        fs::write(&tmp_path, block)?;
+        fs::rename(tmp_path, path)
+    }
+
+    pub(crate) fn get(&self, cid: &Cid) -> io::Result<Vec<u8>> {
+        let block = fs::read(self.block_path(cid))?;
+        if Cid::new(cid.codec(), &block) != *cid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("block {} does not match its hash", cid),
+            ));
+        }
+        Ok(block)
+    }
+
+    fn block_path(&self, cid: &Cid) -> PathBuf {
+        self.dir.join(cid.to_string())
+    }
+}
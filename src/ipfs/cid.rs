@@ -0,0 +1,165 @@
+use sha2::{Digest, Sha256};
+use std::convert::TryInto;
+use std::fmt;
+use std::str::FromStr;
+
+/// the multihash code of sha2-256
+const SHA2_256: u8 = 0x12;
+/// the length of the sha2-256 digest
+const SHA2_256_LEN: u8 = 32;
+/// the multibase prefix of base32 lowercase without padding
+const BASE32_PREFIX: char = 'b';
+const BASE32_ALPHABET: &[u8; 32] = b"abcdefghijklmnopqrstuvwxyz234567";
+
+/// The multicodec of the content addressed by the cid
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Codec {
+    /// the block is the raw bytes of the file
+    Raw,
+    /// the block is a protobuf encoded merkle dag node, which links to other blocks
+    DagPb,
+}
+
+impl Codec {
+    fn code(&self) -> u64 {
+        match self {
+            Codec::Raw => 0x55,
+            Codec::DagPb => 0x70,
+        }
+    }
+
+    fn from_code(code: u64) -> Option<Self> {
+        match code {
+            0x55 => Some(Codec::Raw),
+            0x70 => Some(Codec::DagPb),
+            _ => None,
+        }
+    }
+}
+
+/// An IPFS content identifier, version 1 with a sha2-256 multihash.
+/// Its string representation is in base32, the same as the one used by `ipfs add --cid-version=1`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Cid {
+    codec: Codec,
+    digest: [u8; 32],
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("invalid cid: {0}")]
+pub struct InvalidCid(String);
+
+impl Cid {
+    /// compute the cid of this block
+    pub fn new(codec: Codec, block: &[u8]) -> Self {
+        Self {
+            codec,
+            digest: Sha256::digest(block).into(),
+        }
+    }
+
+    pub fn codec(&self) -> Codec {
+        self.codec
+    }
+
+    /// the binary representation of the cid, which is used in the links of dag-pb nodes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = vec![];
+        encode_varint(1, &mut bytes);
+        encode_varint(self.codec.code(), &mut bytes);
+        bytes.push(SHA2_256);
+        bytes.push(SHA2_256_LEN);
+        bytes.extend_from_slice(&self.digest);
+        bytes
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, InvalidCid> {
+        let invalid = || InvalidCid(format!("{:x?}", bytes));
+        let (version, rest) = decode_varint(bytes).ok_or_else(invalid)?;
+        let (codec, rest) = decode_varint(rest).ok_or_else(invalid)?;
+        if version != 1 {
+            return Err(invalid());
+        }
+        let codec = Codec::from_code(codec).ok_or_else(invalid)?;
+        match rest {
+            [SHA2_256, SHA2_256_LEN, digest @ ..] => Ok(Self {
+                codec,
+                digest: digest.try_into().map_err(|_| invalid())?,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Cid {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", BASE32_PREFIX, encode_base32(&self.to_bytes()))
+    }
+}
+
+impl FromStr for Cid {
+    type Err = InvalidCid;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let encoded = s
+            .strip_prefix(BASE32_PREFIX)
+            .ok_or_else(|| InvalidCid(s.to_string()))?;
+        let bytes = decode_base32(encoded).ok_or_else(|| InvalidCid(s.to_string()))?;
+        Cid::from_bytes(&bytes)
+    }
+}
+
+/// unsigned LEB128, used in cids and protobuf
+pub(crate) fn encode_varint(mut value: u64, buffer: &mut Vec<u8>) {
+    while value >= 0x80 {
+        buffer.push((value as u8 & 0x7f) | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+/// return the decoded value and the remaining bytes
+pub(crate) fn decode_varint(bytes: &[u8]) -> Option<(u64, &[u8])> {
+    let mut value = 0u64;
+    for (i, byte) in bytes.iter().enumerate().take(10) {
+        value |= ((byte & 0x7f) as u64) << (7 * i);
+        if byte & 0x80 == 0 {
+            return Some((value, &bytes[i + 1..]));
+        }
+    }
+    None
+}
+
+fn encode_base32(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity((bytes.len() * 8 + 4) / 5);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for byte in bytes {
+        buffer = (buffer << 8) | *byte as u32;
+        bits += 8;
+        while bits >= 5 {
+            bits -= 5;
+            encoded.push(BASE32_ALPHABET[((buffer >> bits) & 0x1f) as usize] as char);
+        }
+    }
+    if bits > 0 {
+        encoded.push(BASE32_ALPHABET[((buffer << (5 - bits)) & 0x1f) as usize] as char);
+    }
+    encoded
+}
+
+fn decode_base32(encoded: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(encoded.len() * 5 / 8);
+    let mut buffer = 0u32;
+    let mut bits = 0;
+    for c in encoded.bytes() {
+        let value = BASE32_ALPHABET.iter().position(|a| *a == c)? as u32;
+        buffer = (buffer << 5) | value;
+        bits += 5;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((buffer >> bits) as u8);
+        }
+    }
+    Some(bytes)
+}
@@ -0,0 +1,224 @@
+//! Chunk files into a UnixFS DAG, the same layout as `ipfs add --cid-version=1`:
+//! raw leaves of 256KiB and a balanced tree of dag-pb nodes with at most 174 links each.
+use super::cid::{decode_varint, encode_varint, Cid, Codec};
+
+pub(crate) const CHUNK_SIZE: usize = 256 * 1024;
+pub(crate) const MAX_LINKS: usize = 174;
+
+/// the UnixFS data type of a file
+const UNIXFS_FILE: u64 = 2;
+
+/// A block with its cid
+pub(crate) struct Block {
+    pub(crate) cid: Cid,
+    pub(crate) data: Vec<u8>,
+}
+
+/// A node in the DAG which is being built
+struct DagNode {
+    cid: Cid,
+    /// the size of the file content under this node
+    file_size: u64,
+    /// the size of this block and all the blocks under it
+    cumulative_size: u64,
+}
+
+/// A link in a dag-pb node
+pub(crate) struct Link {
+    pub(crate) cid: Cid,
+}
+
+/// Split the file into blocks, the last block is the root of the DAG
+pub(crate) fn chunk(content: &[u8]) -> Vec<Block> {
+    let mut blocks = vec![];
+    let mut nodes: Vec<DagNode> = if content.is_empty() {
+        vec![raw_leaf(&[], &mut blocks)]
+    } else {
+        content
+            .chunks(CHUNK_SIZE)
+            .map(|chunk| raw_leaf(chunk, &mut blocks))
+            .collect()
+    };
+    while nodes.len() > 1 {
+        nodes = nodes
+            .chunks(MAX_LINKS)
+            .map(|children| file_node(children, &mut blocks))
+            .collect();
+    }
+    blocks
+}
+
+fn raw_leaf(chunk: &[u8], blocks: &mut Vec<Block>) -> DagNode {
+    let cid = Cid::new(Codec::Raw, chunk);
+    blocks.push(Block {
+        cid: cid.clone(),
+        data: chunk.to_vec(),
+    });
+    DagNode {
+        cid,
+        file_size: chunk.len() as u64,
+        cumulative_size: chunk.len() as u64,
+    }
+}
+
+/// create a dag-pb node which links to the children
+fn file_node(children: &[DagNode], blocks: &mut Vec<Block>) -> DagNode {
+    let file_size = children.iter().map(|child| child.file_size).sum();
+
+    let mut unixfs = vec![];
+    encode_varint_field(1, UNIXFS_FILE, &mut unixfs);
+    encode_varint_field(3, file_size, &mut unixfs);
+    for child in children {
+        encode_varint_field(4, child.file_size, &mut unixfs);
+    }
+
+    // the links are written before the data, as required by the dag-pb spec
+    let mut node = vec![];
+    for child in children {
+        let mut link = vec![];
+        encode_bytes_field(1, &child.cid.to_bytes(), &mut link);
+        encode_bytes_field(2, &[], &mut link);
+        encode_varint_field(3, child.cumulative_size, &mut link);
+        encode_bytes_field(2, &link, &mut node);
+    }
+    encode_bytes_field(1, &unixfs, &mut node);
+
+    let cid = Cid::new(Codec::DagPb, &node);
+    let cumulative_size =
+        node.len() as u64 + children.iter().map(|c| c.cumulative_size).sum::<u64>();
+    blocks.push(Block {
+        cid: cid.clone(),
+        data: node,
+    });
+    DagNode {
+        cid,
+        file_size,
+        cumulative_size,
+    }
+}
+
+/// Decode a dag-pb node into its links and the file content it holds inline, if any
+pub(crate) fn decode_node(block: &[u8]) -> Option<(Vec<Link>, Vec<u8>)> {
+    let mut links = vec![];
+    let mut inline_data = vec![];
+    for (field, value) in decode_fields(block)? {
+        match (field, value) {
+            (2, FieldValue::Bytes(link)) => {
+                let hash = decode_fields(link)?
+                    .into_iter()
+                    .find_map(|(field, value)| match (field, value) {
+                        (1, FieldValue::Bytes(hash)) => Some(hash),
+                        _ => None,
+                    })?;
+                links.push(Link {
+                    cid: Cid::from_bytes(hash).ok()?,
+                });
+            }
+            (1, FieldValue::Bytes(unixfs)) => {
+                for (field, value) in decode_fields(unixfs)? {
+                    if let (2, FieldValue::Bytes(data)) = (field, value) {
+                        inline_data.extend_from_slice(data);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    Some((links, inline_data))
+}
+
+enum FieldValue<'a> {
+    /// the value of varint fields are not needed when reading the file content
+    Varint,
+    Bytes(&'a [u8]),
+}
+
+/// decode the protobuf fields, only varint and length delimited wire types are supported
+fn decode_fields(mut bytes: &[u8]) -> Option<Vec<(u64, FieldValue)>> {
+    let mut fields = vec![];
+    while !bytes.is_empty() {
+        let (key, rest) = decode_varint(bytes)?;
+        let (field, wire_type) = (key >> 3, key & 0x7);
+        match wire_type {
+            0 => {
+                let (_value, rest) = decode_varint(rest)?;
+                fields.push((field, FieldValue::Varint));
+                bytes = rest;
+            }
+            2 => {
+                let (len, rest) = decode_varint(rest)?;
+                let len = len as usize;
+                if rest.len() < len {
+                    return None;
+                }
+                fields.push((field, FieldValue::Bytes(&rest[..len])));
+                bytes = &rest[len..];
+            }
+            _ => return None,
+        }
+    }
+    Some(fields)
+}
+
+fn encode_varint_field(field: u64, value: u64, buffer: &mut Vec<u8>) {
+    encode_varint(field << 3, buffer);
+    encode_varint(value, buffer);
+}
+
+fn encode_bytes_field(field: u64, value: &[u8], buffer: &mut Vec<u8>) {
+    encode_varint(field << 3 | 2, buffer);
+    encode_varint(value.len() as u64, buffer);
+    buffer.extend_from_slice(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// the cid of the root, as printed by `ipfs add --cid-version 1 --raw-leaves`
+    fn root_cid(content: &[u8]) -> String {
+        chunk(content).last().expect("a root").cid.to_string()
+    }
+
+    #[test]
+    fn single_chunk_is_a_raw_leaf() {
+        assert_eq!(
+            root_cid(b""),
+            "bafkreihdwdcefgh4dqkjv67uzcmw7ojee6xedzdetojuzjevtenxquvyku"
+        );
+        assert_eq!(
+            root_cid(b"hello world"),
+            "bafkreifzjut3te2nhyekklss27nh3k72ysco7y32koao5eei66wof36n5e"
+        );
+    }
+
+    #[test]
+    fn chunked_file_has_a_dag_pb_root() {
+        let content = vec![0u8; CHUNK_SIZE + 1];
+        assert_eq!(
+            root_cid(&content),
+            "bafybeigllfqgfpqydppr6cmv56g7ax4wyhruzswvcefv6j5kj77nzttfki"
+        );
+    }
+
+    #[test]
+    fn chunked_file_is_read_back() {
+        let content: Vec<u8> = (0..CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+        let blocks = chunk(&content);
+        let root = blocks.last().expect("a root");
+        let (links, inline_data) = decode_node(&root.data).expect("a dag-pb node");
+        assert!(inline_data.is_empty());
+        let leaves: Vec<u8> = links
+            .iter()
+            .flat_map(|link| {
+                blocks
+                    .iter()
+                    .find(|block| block.cid == link.cid)
+                    .expect("a linked block")
+                    .data
+                    .clone()
+            })
+            .collect();
+        assert_eq!(leaves, content);
+    }
+}
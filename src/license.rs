@@ -0,0 +1,132 @@
+//! Offline verification of the license of the desktop app.
+//!
+//! The license is a json file in `~/.config/madnotes/license` which contains the licensee,
+//! the expiry and an ed25519 signature of both, made with the secret key of the issuer.
+//! It is verified with the public key embedded in the binary, so no network access is needed.
+use crate::config::config_dir;
+use ed25519_dalek::{Keypair, PublicKey, Signature, Signer, Verifier};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+use thiserror::Error;
+
+/// The public key of the issuer in base64, set with `MADNOTES_LICENSE_PUBLIC_KEY` at build time.
+/// The release build of the desktop app fails without it, while a debug build accepts no license.
+/// Set it to the key created by `issue_license generate-key` to test.
+const PUBLIC_KEY: Option<&str> = option_env!("MADNOTES_LICENSE_PUBLIC_KEY");
+
+/// the name of the license file in the config directory
+const LICENSE_FILE: &str = "license";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct License {
+    pub(crate) licensee: String,
+    /// the unix time in seconds when the license expires, None if it never expires
+    pub(crate) expires_at: Option<u64>,
+    /// the base64 of the signature of the licensee and the expiry
+    signature: String,
+}
+
+#[derive(Debug, Error)]
+pub(crate) enum LicenseError {
+    #[error("there is no license")]
+    Missing,
+    #[error("invalid license: {0}")]
+    Invalid(String),
+    #[error("the license is not signed by the issuer")]
+    BadSignature,
+    #[error("the license has expired")]
+    Expired,
+    #[error("io error: {0}")]
+    IoError(#[from] io::Error),
+}
+
+impl License {
+    /// sign a new license, this is used by the issue_license binary
+    #[allow(unused)]
+    pub(crate) fn issue(keypair: &Keypair, licensee: &str, expires_at: Option<u64>) -> Self {
+        let signature = keypair.sign(&signed_message(licensee, expires_at));
+        Self {
+            licensee: licensee.to_string(),
+            expires_at,
+            signature: base64::encode(signature.to_bytes()),
+        }
+    }
+
+    /// check the signature with the embedded public key, then the expiry
+    pub(crate) fn verify(&self) -> Result<(), LicenseError> {
+        let public_key = PUBLIC_KEY
+            .ok_or_else(|| LicenseError::Invalid("this build has no public key".to_string()))?;
+        let public_key = base64::decode(public_key)
+            .ok()
+            .and_then(|bytes| PublicKey::from_bytes(&bytes).ok())
+            .ok_or_else(|| LicenseError::Invalid("invalid public key".to_string()))?;
+        let signature = base64::decode(&self.signature)
+            .ok()
+            .and_then(|bytes| Signature::try_from(&bytes[..]).ok())
+            .ok_or(LicenseError::BadSignature)?;
+        public_key
+            .verify(&signed_message(&self.licensee, self.expires_at), &signature)
+            .map_err(|_| LicenseError::BadSignature)?;
+        match self.expires_at {
+            Some(expires_at) if expires_at <= now() => Err(LicenseError::Expired),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn from_json(json: &str) -> Result<Self, LicenseError> {
+        serde_json::from_str(json.trim()).map_err(|e| LicenseError::Invalid(e.to_string()))
+    }
+
+    pub(crate) fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("must serialize")
+    }
+}
+
+/// `~/.config/madnotes/license` on linux
+pub(crate) fn license_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join(LICENSE_FILE))
+}
+
+/// read and verify the installed license
+pub(crate) fn load() -> Result<License, LicenseError> {
+    let file = license_file().ok_or(LicenseError::Missing)?;
+    let json = match fs::read_to_string(file) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(LicenseError::Missing),
+        Err(e) => return Err(e.into()),
+    };
+    let license = License::from_json(&json)?;
+    license.verify()?;
+    Ok(license)
+}
+
+/// verify the license then store it, replacing the installed one
+pub(crate) fn install(json: &str) -> Result<License, LicenseError> {
+    let license = License::from_json(json)?;
+    license.verify()?;
+    let file = license_file()
+        .ok_or_else(|| LicenseError::Invalid("there is no config directory".to_string()))?;
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(file, license.to_json())?;
+    Ok(license)
+}
+
+/// the bytes which are signed, the fields are separated by new lines
+fn signed_message(licensee: &str, expires_at: Option<u64>) -> Vec<u8> {
+    let expires_at = expires_at.map(|e| e.to_string()).unwrap_or_default();
+    format!("madnotes-license\n{}\n{}", licensee, expires_at).into_bytes()
+}
+
+/// the current unix time in seconds
+pub(crate) fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
@@ -1,40 +1,76 @@
 //#![deny(warnings)]
+use bridge::BridgeResponse;
+use client::Invocation;
+use command::Desktop;
 use futures::stream::StreamExt;
 use futures::TryStreamExt;
 use hyper::Body;
-use native_dialog::FileDialog;
 use route::Route;
 use sauron::Render;
+use serde::Deserialize;
+use std::io;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use structopt::StructOpt;
 use tokio::sync::oneshot;
 use web_view::*;
+use window::{NativeWindow, WindowGeometry};
+use workspace::Workspace;
 
+mod bridge;
+mod command;
+mod config;
+mod ipfs;
+mod license;
+mod publish;
+mod recent;
+mod recovery;
 mod route;
 mod serve_files;
 mod server;
+mod version;
+mod watcher;
+mod window;
+mod workspace;
 
-//TODO: use the license verifier here
-// The file is stored in ~/.config/
-fn check_and_verify_license() -> bool {
-    false
+/// Create and share notes
+#[derive(StructOpt, Debug)]
+#[structopt(name = "madnotes")]
+struct Opt {
+    /// the note to open, or the directory to use as the workspace.
+    /// The workspace of a note is its directory, it defaults to the current directory.
+    #[structopt(parse(from_os_str))]
+    path: Option<PathBuf>,
 }
 
 #[tokio::main]
 async fn main() {
-    let settings = client::Settings::default();
-    let app_title = if check_and_verify_license() {
-        settings.app_title.clone()
-    } else {
-        format!("{} (UNREGISTERED)", settings.app_title.clone())
-    };
+    let opt = Opt::from_args();
+    let mut settings = client::Settings::default();
 
     #[cfg(feature = "open-ports")]
     let (socket_tx, socket_rx) = oneshot::channel();
     #[cfg(feature = "open-ports")]
     let (shutdown_tx, shutdown_rx) = oneshot::channel::<()>();
 
+    let (workspace, note) = match open_path(opt.path.as_deref()) {
+        Ok(opened) => opened,
+        Err(e) => {
+            eprintln!("unable to open {}: {}", display(opt.path.as_deref()), e);
+            std::process::exit(1);
+        }
+    };
+    // the note is loaded by the client with its absolute path
+    settings.open_note = note.map(|note| note.display().to_string());
+
+    let mut state = server::State::new(settings.clone(), workspace);
+    // the notes of the desktop app are not limited to the workspace
+    state.recovery = recovery::Recovery::for_desktop();
+    let desktop = Desktop::new(settings.app_title.clone(), state.clone());
+    let app_title = desktop.app_title();
+
     #[cfg(feature = "open-ports")]
-    tokio::spawn(server::serve_ephemeral(settings, socket_tx, shutdown_rx));
+    tokio::spawn(server::serve_ephemeral(state, socket_tx, shutdown_rx));
 
     #[cfg(feature = "open-ports")]
     let socket = socket_rx.await.expect("must get the socket address");
@@ -52,16 +88,30 @@ async fn main() {
         Content::Html(html)
     };
 
-    web_view::builder()
+    let geometry = window::load();
+    let (width, height) = geometry
+        .map(|geometry| (geometry.width, geometry.height))
+        .unwrap_or(window::DEFAULT_SIZE);
+    let webview = web_view::builder()
         .title(&app_title)
         .content(content)
-        .size(1200, 600)
+        .size(width, height)
         .resizable(true)
         .debug(true)
-        .user_data(())
+        .user_data(desktop)
         .invoke_handler(move |webview, arg| invoke_handler(webview, arg))
-        .run()
+        .build()
         .expect("must launch the web view");
+    // the builder can only set the size, the position is restored natively
+    let window = NativeWindow::of(&webview);
+    if let (Some(window), Some(geometry)) = (&window, geometry) {
+        window.set_geometry(&window::clamp(geometry, &window.screens()));
+    }
+    let (mut desktop, geometry) = run(webview, window).expect("must run the web view");
+    desktop.save_on_close();
+    if let Some(geometry) = &geometry {
+        window::store(geometry);
+    }
 
     println!("shuting it down..");
 
@@ -69,50 +119,121 @@ async fn main() {
     shutdown_tx.send(()).expect("must send the shutdown signal");
 }
 
-/// The this handles the call from javascript, which contains the serialized object Route based on
-/// the route methods we then map it to their corresponding functions that needs to be done.
+/// Run the webview until its window is closed, and return the last geometry of the window.
+/// The geometry is read after every event, since the window is gone once it is closed.
+fn run(
+    mut webview: WebView<'_, Desktop>,
+    window: Option<NativeWindow>,
+) -> WVResult<(Desktop, Option<WindowGeometry>)> {
+    let mut geometry = None;
+    loop {
+        match webview.step() {
+            Some(Ok(())) => {
+                if let Some(window) = &window {
+                    geometry = window.geometry().or(geometry);
+                }
+            }
+            Some(Err(e)) => return Err(e),
+            None => return Ok((webview.into_inner(), geometry)),
+        }
+    }
+}
+
+/// The workspace and the note to open at startup.
+/// A note is opened in the workspace of its directory, while a directory is the workspace itself.
+fn open_path(path: Option<&Path>) -> io::Result<(Workspace, Option<PathBuf>)> {
+    let path = match path {
+        Some(path) => path.canonicalize()?,
+        None => return Ok((Workspace::current_dir()?, None)),
+    };
+    if path.is_dir() {
+        Ok((Workspace::new(&path)?, None))
+    } else {
+        let dir = path.parent().unwrap_or_else(|| Path::new("/"));
+        Ok((Workspace::new(dir)?, Some(path)))
+    }
+}
+
+fn display(path: Option<&Path>) -> String {
+    path.map(|path| path.display().to_string())
+        .unwrap_or_else(|| "the current directory".to_string())
+}
+
+/// The message sent from javascript through `invoke`
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum External {
+    /// a command to be executed natively, sent by the client with `invoke_command`
+    Command(Invocation),
+    /// a request intercepted by the fetch override
+    Route(Route),
+}
+
+/// The this handles the call from javascript, which is either a command or the serialized object
+/// Route based on the route methods we then map it to their corresponding functions that needs to
+/// be done.
 ///
 /// Along with the route is a callback_id which is essentially a pointer to a stack in the
 /// javascript side, where an array of callback is maintained. This callback_id corresponds to the
 /// index in the array of the callbacks which we then call along with the argument payload.
+/// Commands have their own callbacks which are called with `commandCallback`.
 ///
-fn invoke_handler(webview: &mut WebView<'_, ()>, arg: &str) -> Result<(), Error> {
-    match arg {
-        "open" => {
-            let path = FileDialog::new()
-                .set_location("~/Desktop")
-                .add_filter("PNG Image", &["png"])
-                .add_filter("JPEG Image", &["jpg", "jpeg"])
-                .show_open_single_file()
-                .unwrap();
-            println!("path: {:?}", path);
+fn invoke_handler(webview: &mut WebView<'_, Desktop>, arg: &str) -> Result<(), Error> {
+    // there is no callback to reply to if the message itself can not be decoded
+    let external: External = match serde_json::from_str(arg) {
+        Ok(external) => external,
+        Err(e) => {
+            eprintln!("invalid message from the webview: {}", e);
+            return Ok(());
+        }
+    };
+    match external {
+        External::Command(invocation) => {
+            let result = command::execute(webview, invocation.command);
+            if let Err(e) = &result {
+                eprintln!("command error: {}", e.message);
+            }
+            command_callback(webview, invocation.callback_id, &result);
         }
-        _ => {
-            let route: Route = serde_json::from_str(arg).expect("Error decoding json");
-            println!("url: {}", route.url);
-            let raw_response =
-                serve_files::raw_serve(&client::Settings::default(), &*route.path_and_query())
-                    .expect("must not fail request");
-
-            response_callback(
-                webview,
-                route.callback_id,
-                base64::encode(&raw_response.content),
-            );
+        External::Route(route) => {
+            let response = bridge::serve(&webview.user_data().state, &route);
+            response_callback(webview, route.callback_id, &response);
         }
     }
     Ok(())
 }
 
+fn command_callback<'a, T>(
+    webview: &mut WebView<'a, T>,
+    callback_id: usize,
+    result: &client::CommandResult,
+) {
+    // the json is passed as a javascript string literal
+    let json = serde_json::to_string(result).and_then(|response| serde_json::to_string(&response));
+    match json {
+        Ok(json) => {
+            if let Err(e) = webview.eval(&format!("commandCallback({},{})", callback_id, json)) {
+                eprintln!("unable to send the command result to the webview: {}", e);
+            }
+        }
+        Err(e) => eprintln!("unable to serialize the command result: {}", e),
+    }
+}
+
 fn response_callback<'a, T>(
     webview: &mut WebView<'a, T>,
     callback_id: usize,
-    response_payload: String,
+    response: &BridgeResponse,
 ) {
-    webview
-        .eval(&format!(
-            "responseCallback({},\"{}\")",
-            callback_id, response_payload
-        ))
-        .expect("must eval");
+    // the json is passed as a javascript string literal
+    let json =
+        serde_json::to_string(response).and_then(|response| serde_json::to_string(&response));
+    match json {
+        Ok(json) => {
+            if let Err(e) = webview.eval(&format!("responseCallback({},{})", callback_id, json)) {
+                eprintln!("unable to send the response to the webview: {}", e);
+            }
+        }
+        Err(e) => eprintln!("unable to serialize the response: {}", e),
+    }
 }
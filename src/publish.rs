@@ -0,0 +1,124 @@
+//! Publish a note into the blockstore together with the local files it refers to.
+//!
+//! The links to the local files are rewritten to their cid in the gateway, so the published
+//! note is self-contained. Every referenced file must reside inside the workspace, the links to
+//! files which don't exist are left as is.
+use crate::ipfs::Blockstore;
+use crate::server::gateway;
+use crate::server::ServiceError;
+use crate::workspace::Workspace;
+use client::links;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+
+/// The result of publishing a note
+#[derive(Debug, Serialize)]
+pub struct Published {
+    /// the cid of the note with its links rewritten
+    pub cid: String,
+    /// the referenced files which are published along with the note
+    pub files: Vec<PublishedFile>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PublishedFile {
+    /// the path of the file relative to the workspace
+    pub path: String,
+    pub cid: String,
+}
+
+pub(crate) fn publish(
+    workspace: &Workspace,
+    blockstore: &Blockstore,
+    note: &str,
+) -> Result<Published, ServiceError> {
+    let content = workspace.read_note(note)?;
+    // a link which can not be located could refer to a file outside of the workspace
+    let unlocated = |e: links::UnlocatedLink| ServiceError::BadRequest(format!("{}: {}", note, e));
+    // the same file could be referenced more than once
+    let mut published: BTreeMap<String, String> = BTreeMap::new();
+    for reference in links::references(&content).map_err(unlocated)? {
+        let relative = match workspace.linked_path(note, &reference.dest)? {
+            Some(relative) if !published.contains_key(&relative) => relative,
+            _ => continue,
+        };
+        let file = workspace.resolve(&relative)?;
+        if !file.is_file() {
+            eprintln!(
+                "{} refers to {} which does not exist, the link is not rewritten",
+                note, reference.dest
+            );
+            continue;
+        }
+        let cid = blockstore.add(&fs::read(file)?)?;
+        published.insert(relative, cid.to_string());
+    }
+
+    let rewritten = links::rewrite_links(&content, |dest| {
+        let relative = workspace.linked_path(note, dest).ok()??;
+        let cid = published.get(&relative)?;
+        let (_path, fragment) = links::split_fragment(dest);
+        // the query is replaced by the one in the gateway url
+        let fragment = if fragment.starts_with('#') {
+            fragment
+        } else {
+            ""
+        };
+        Some(format!("{}{}", gateway::file_url(cid, &relative), fragment))
+    })
+    .map_err(unlocated)?;
+    let cid = blockstore.add(rewritten.as_bytes())?;
+
+    Ok(Published {
+        cid: cid.to_string(),
+        files: published
+            .into_iter()
+            .map(|(path, cid)| PublishedFile { path, cid })
+            .collect(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_the_existing_local_files_are_published() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::create_dir(dir.path().join("sub")).unwrap();
+        fs::write(dir.path().join("sub/my plot.png"), "plot").unwrap();
+        fs::write(dir.path().join("sub/absolute.png"), "absolute").unwrap();
+        let content = "![plot](my%20plot.png#top) ![absolute](/absolute.png) [gone](missing.png)";
+        fs::write(dir.path().join("sub/note.md"), content).unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        let blockstore = Blockstore::for_workspace(&workspace);
+
+        let published = publish(&workspace, &blockstore, "sub/note.md").unwrap();
+        assert_eq!(published.files.len(), 1);
+        let plot = &published.files[0];
+        assert_eq!(plot.path, "sub/my plot.png");
+
+        let cid: crate::ipfs::Cid = published.cid.parse().unwrap();
+        let note = String::from_utf8(blockstore.cat(&cid).unwrap()).unwrap();
+        assert_eq!(
+            note,
+            format!(
+                "![plot]({}#top) ![absolute](/absolute.png) [gone](missing.png)",
+                gateway::file_url(&plot.cid, "sub/my plot.png")
+            )
+        );
+    }
+
+    #[test]
+    fn files_outside_of_the_workspace_are_refused() {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("note.md"), "![secret](../secret.png)").unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        let blockstore = Blockstore::for_workspace(&workspace);
+        assert!(matches!(
+            publish(&workspace, &blockstore, "note.md"),
+            Err(ServiceError::Forbidden(_))
+        ));
+    }
+}
@@ -0,0 +1,99 @@
+//! The most recently used notes, stored as json in the user config directory.
+//!
+//! The desktop app keeps a single list of absolute paths, while the server keeps a list of
+//! paths relative to the workspace for each workspace.
+use crate::config::{config_dir, file_key};
+use crate::workspace::Workspace;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// the maximum number of notes kept in the list
+const MAX_RECENT: usize = 10;
+
+#[derive(Debug)]
+pub(crate) struct RecentFiles {
+    /// where the list is stored, it is only kept in memory if there is no config directory
+    file: Option<PathBuf>,
+    /// the most recent first
+    paths: Vec<String>,
+}
+
+impl RecentFiles {
+    /// the single list of the desktop app
+    /// This is used by the desktop binary only
+    #[allow(unused)]
+    pub(crate) fn for_desktop() -> Self {
+        Self::load(config_dir().map(|dir| dir.join("recent.json")))
+    }
+
+    /// the list of the notes in this workspace when served by the server
+    pub(crate) fn for_workspace(workspace: &Workspace) -> Self {
+        let key = file_key(&workspace.root().to_string_lossy());
+        Self::load(config_dir().map(|dir| dir.join("recent").join(format!("{}.json", key))))
+    }
+
+    /// a list which is not stored
+    #[cfg(test)]
+    pub(crate) fn in_memory() -> Self {
+        Self::load(None)
+    }
+
+    /// a missing or invalid file is an empty list
+    fn load(file: Option<PathBuf>) -> Self {
+        let paths = file
+            .as_ref()
+            .and_then(|file| fs::read_to_string(file).ok())
+            .and_then(|json| serde_json::from_str(&json).ok())
+            .unwrap_or_default();
+        Self { file, paths }
+    }
+
+    /// the list without the files which no longer exist
+    pub(crate) fn list(&mut self, exists: impl Fn(&str) -> bool) -> Vec<String> {
+        let count = self.paths.len();
+        self.paths.retain(|path| exists(path));
+        if self.paths.len() != count {
+            self.store();
+        }
+        self.paths.clone()
+    }
+
+    pub(crate) fn add(&mut self, path: &str) {
+        self.paths.retain(|recent| recent != path);
+        self.paths.insert(0, path.to_string());
+        self.paths.truncate(MAX_RECENT);
+        self.store();
+    }
+
+    pub(crate) fn remove(&mut self, path: &str) {
+        self.paths.retain(|recent| recent != path);
+        self.store();
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.paths.clear();
+        self.store();
+    }
+
+    /// failing to store the list is not fatal, it is still kept in memory
+    fn store(&self) {
+        if let Some(file) = &self.file {
+            if let Err(e) = self.write(file) {
+                eprintln!(
+                    "unable to store the recent files in {}: {}",
+                    file.display(),
+                    e
+                );
+            }
+        }
+    }
+
+    fn write(&self, file: &Path) -> io::Result<()> {
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let json = serde_json::to_string_pretty(&self.paths)?;
+        fs::write(file, json)
+    }
+}
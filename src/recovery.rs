@@ -0,0 +1,118 @@
+//! The snapshots of the unsaved buffers, stored in the user config directory.
+//!
+//! Each buffer is stored in its own json file, named after the session of the app and its note.
+//! They are removed once the buffer is saved, so the snapshots found at startup are the changes
+//! that would have been lost. A session only replaces its own snapshots, so the ones of the
+//! previous sessions are kept until they are restored or discarded.
+use crate::config::{config_dir, file_key};
+use crate::workspace::Workspace;
+use client::Snapshot;
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// the file name of the buffer which is not saved to a note yet
+const UNTITLED: &str = "untitled";
+
+#[derive(Debug, Clone)]
+pub(crate) struct Recovery {
+    /// the snapshots are not kept if there is no config directory
+    dir: Option<PathBuf>,
+}
+
+impl Recovery {
+    /// the snapshots of the desktop app, where the notes are absolute paths
+    /// This is used by the desktop binary only
+    #[allow(unused)]
+    pub(crate) fn for_desktop() -> Self {
+        Self {
+            dir: config_dir().map(|dir| dir.join("recovery").join("desktop")),
+        }
+    }
+
+    /// the snapshots of the notes in this workspace when served by the server
+    pub(crate) fn for_workspace(workspace: &Workspace) -> Self {
+        let key = file_key(&workspace.root().to_string_lossy());
+        Self {
+            dir: config_dir().map(|dir| dir.join("recovery").join(key)),
+        }
+    }
+
+    /// the snapshots are not kept
+    #[cfg(test)]
+    pub(crate) fn in_memory() -> Self {
+        Self { dir: None }
+    }
+
+    /// the snapshots, the most recent first. Invalid files are skipped.
+    pub(crate) fn list(&self) -> io::Result<Vec<Snapshot>> {
+        let dir = match &self.dir {
+            Some(dir) if dir.is_dir() => dir,
+            _ => return Ok(vec![]),
+        };
+        let mut snapshots = vec![];
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            let snapshot = fs::read_to_string(&path)
+                .ok()
+                .and_then(|json| serde_json::from_str::<Snapshot>(&json).ok());
+            match snapshot {
+                Some(snapshot) => snapshots.push(snapshot),
+                None => eprintln!("skipping invalid snapshot {}", path.display()),
+            }
+        }
+        snapshots.sort_by(|a, b| b.saved_at.cmp(&a.saved_at));
+        Ok(snapshots)
+    }
+
+    /// replace the snapshot of the note taken in this session
+    pub(crate) fn store(&self, session: &str, note: Option<&str>, content: &str) -> io::Result<()> {
+        let file = self.snapshot_file(session, note)?;
+        if let Some(parent) = file.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let snapshot = Snapshot {
+            session: session.to_string(),
+            note: note.map(ToString::to_string),
+            content: content.to_string(),
+            saved_at: now(),
+        };
+        fs::write(file, serde_json::to_string_pretty(&snapshot)?)
+    }
+
+    /// removing a snapshot which does not exist is not an error
+    pub(crate) fn remove(&self, session: &str, note: Option<&str>) -> io::Result<()> {
+        match fs::remove_file(self.snapshot_file(session, note)?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    fn snapshot_file(&self, session: &str, note: Option<&str>) -> io::Result<PathBuf> {
+        let dir = self.dir.as_ref().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::NotFound, "there is no config directory")
+        })?;
+        if !is_session(session) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("invalid session: {:?}", session),
+            ));
+        }
+        let name = note.map(file_key).unwrap_or_else(|| UNTITLED.to_string());
+        Ok(dir.join(format!("{}-{}.json", session, name)))
+    }
+}
+
+/// the session is a part of the file name, so it is only made of letters and digits
+pub(crate) fn is_session(session: &str) -> bool {
+    !session.is_empty() && session.chars().all(|c| c.is_ascii_alphanumeric())
+}
+
+/// the current unix time in seconds
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or(0)
+}
@@ -1,13 +1,15 @@
-use crate::server;
-use hyper::{Body, Method, Request};
+use crate::server::{self, ServiceError};
+use hyper::header::{HeaderMap, HeaderName, HeaderValue};
+use hyper::{Method, Request};
 use serde::Deserialize;
-use std::borrow::Cow;
 use std::collections::HashMap;
 use std::str::FromStr;
 
+/// A request intercepted by the fetch and XmlHttpRequest override in the webview
 #[derive(Debug, Deserialize)]
 pub struct Route {
     pub method: String,
+    /// the base64 of the request body
     pub body: Option<String>,
     pub url: String,
     pub callback_id: usize,
@@ -15,24 +17,41 @@ pub struct Route {
 }
 
 impl Route {
-    pub fn to_request(&self) -> Request<Body> {
-        let body = if let Some(body) = &self.body {
-            Body::from(body.to_string())
-        } else {
-            Body::from(String::new())
-        };
+    pub fn method(&self) -> Result<Method, ServiceError> {
+        Method::from_str(&self.method)
+            .map_err(|_| ServiceError::BadRequest(format!("invalid method: {}", self.method)))
+    }
 
-        let method = Method::from_str(&self.method).expect("method name must be correct");
+    pub fn headers(&self) -> Result<HeaderMap, ServiceError> {
+        let mut headers = HeaderMap::new();
+        for (name, value) in self.headers.iter().flatten() {
+            let name = HeaderName::from_str(name)
+                .map_err(|_| ServiceError::BadRequest(format!("invalid header: {}", name)))?;
+            let value = HeaderValue::from_str(value).map_err(|_| {
+                ServiceError::BadRequest(format!("invalid header value: {}", value))
+            })?;
+            headers.append(name, value);
+        }
+        Ok(headers)
+    }
 
-        Request::builder()
-            .uri(&self.url)
-            .method(method)
-            .body(body)
-            .expect("must be converted to request")
+    /// the decoded body, empty if the request has none
+    pub fn body(&self) -> Result<Vec<u8>, ServiceError> {
+        match &self.body {
+            Some(body) => base64::decode(body).map_err(|e| ServiceError::BadRequest(e.to_string())),
+            None => Ok(vec![]),
+        }
     }
 
-    pub fn path_and_query(&self) -> String {
-        let request = self.to_request();
-        server::extract_path_and_query(&request).to_string()
+    /// the decoded path and the query of the url, which are split the same way as in the server
+    pub fn path_and_query(&self) -> Result<(String, String), ServiceError> {
+        let request = Request::builder()
+            .uri(&self.url)
+            .body(())
+            .map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+        Ok((
+            server::extract_path(&request).to_string(),
+            server::extract_query(&request).to_string(),
+        ))
     }
 }
@@ -1,60 +1,285 @@
 use crate::server::page;
 use crate::server::ServiceError;
 use client::Settings;
+use hyper::header::{HeaderMap, ACCEPT_ENCODING, IF_NONE_MATCH};
+use hyper::StatusCode;
 use sauron::prelude::*;
+use std::borrow::Cow;
+use std::fs::File;
+use std::io::{self, Read};
 
 #[derive(Debug)]
 pub struct RawResponse<'a> {
-    pub content: Vec<u8>,
+    pub status: StatusCode,
+    pub content: Content,
     pub headers: Vec<(&'a str, String)>,
 }
 
+/// The body of a response
+#[derive(Debug)]
+pub enum Content {
+    Bytes(Cow<'static, [u8]>),
+    /// a file which is sent from its current position, up to this number of bytes.
+    /// The server streams it, so large files are not read into memory.
+    File(File, u64),
+}
+
+impl Content {
+    /// the whole body, for the responses which can not be streamed such as the desktop bridge
+    /// This is used by the desktop binary only
+    #[allow(unused)]
+    pub(crate) fn into_bytes(self) -> io::Result<Vec<u8>> {
+        match self {
+            Content::Bytes(bytes) => Ok(bytes.into_owned()),
+            Content::File(file, len) => {
+                let mut bytes = Vec::with_capacity(len as usize);
+                file.take(len).read_to_end(&mut bytes)?;
+                Ok(bytes)
+            }
+        }
+    }
+}
+
 impl<'a> RawResponse<'a> {
-    fn new(content: Vec<u8>, headers: Vec<(&'a str, String)>) -> Self {
-        Self { content, headers }
+    pub(crate) fn new(content: Vec<u8>, headers: Vec<(&'a str, String)>) -> Self {
+        Self {
+            status: StatusCode::OK,
+            content: Content::Bytes(Cow::Owned(content)),
+            headers,
+        }
+    }
+
+    /// the content is embedded in the binary, so there is no need to copy it
+    pub(crate) fn from_static(content: &'static [u8], headers: Vec<(&'a str, String)>) -> Self {
+        Self {
+            status: StatusCode::OK,
+            content: Content::Bytes(Cow::Borrowed(content)),
+            headers,
+        }
+    }
+
+    /// send `len` bytes of the file from its current position
+    pub(crate) fn from_file(file: File, len: u64, headers: Vec<(&'a str, String)>) -> Self {
+        Self {
+            status: StatusCode::OK,
+            content: Content::File(file, len),
+            headers,
+        }
+    }
+
+    pub(crate) fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+/// A client asset embedded in the binary, together with its precompressed variants
+pub(crate) struct Asset {
+    path: &'static str,
+    content_type: &'static str,
+    /// the hash of the uncompressed content, computed at build time
+    hash: &'static str,
+    identity: &'static [u8],
+    gzip: &'static [u8],
+    brotli: &'static [u8],
+}
+
+// generated by build.rs
+include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Encoding {
+    Identity,
+    Gzip,
+    Brotli,
+}
+
+impl Asset {
+    fn content(&self, encoding: Encoding) -> &'static [u8] {
+        match encoding {
+            Encoding::Identity => self.identity,
+            Encoding::Gzip => self.gzip,
+            Encoding::Brotli => self.brotli,
+        }
+    }
+
+    /// each encoding is a different representation, so each has its own strong ETag
+    fn etag(&self, encoding: Encoding) -> String {
+        match encoding {
+            Encoding::Identity => format!("\"{}\"", self.hash),
+            Encoding::Gzip => format!("\"{}-gz\"", self.hash),
+            Encoding::Brotli => format!("\"{}-br\"", self.hash),
+        }
+    }
+
+    /// the client js and wasm must always match each other, so they are revalidated on every
+    /// load, which is cheap with the ETag. The font and favicon never change.
+    fn cache_control(&self) -> &'static str {
+        if self.path.starts_with("/pkg/") {
+            "no-cache"
+        } else {
+            "public, max-age=604800"
+        }
     }
 }
 
-pub(crate) fn client_bg_wasm_content() -> Vec<u8> {
-    include_bytes!("../client/pkg/client_bg.wasm").to_vec()
+fn asset(path: &str) -> Option<&'static Asset> {
+    ASSETS.iter().find(|asset| asset.path == path)
 }
 
-pub(crate) fn favicon_ico_content() -> Vec<u8> {
-    include_bytes!("../favicon.ico").to_vec()
+/// The index page and the assets of the app, which are the only responses other sites can read
+pub(crate) fn is_app_path(path: &str) -> bool {
+    path == "/" || asset(path).is_some()
 }
+
+fn asset_content(path: &str) -> &'static [u8] {
+    asset(path)
+        .map(|asset| asset.identity)
+        .expect("the asset is listed in build.rs")
+}
+
 pub(crate) fn client_js_content() -> &'static str {
     include_str!("../client/pkg/client.js")
 }
 
-pub(crate) fn font_content() -> Vec<u8> {
-    include_bytes!("../assets/JuliaMono-Light.woff2").to_vec()
+pub(crate) fn font_content() -> &'static [u8] {
+    asset_content("/assets/JuliaMono-Light.woff2")
 }
 
 pub fn raw_serve<'a>(
     settings: &Settings,
-    path_and_query: &str,
+    path: &str,
+    headers: &HeaderMap,
 ) -> Result<RawResponse<'a>, ServiceError> {
-    match &*path_and_query {
+    match path {
         "/" => {
             let index_page = page::index(settings).render_to_string();
             Ok(RawResponse::new(index_page.into(), vec![]))
         }
-        "/favicon.ico" => Ok(RawResponse::new(
-            favicon_ico_content().into(),
-            vec![("Content-Type", "image/x-icon".to_string())],
-        )),
-        "/pkg/client.js" => Ok(RawResponse::new(
-            client_js_content().into(),
-            vec![("Content-Type", "text/javascript; charset=UTF-8".to_string())],
-        )),
-        "/pkg/client_bg.wasm" => Ok(RawResponse::new(
-            client_bg_wasm_content().into(),
-            vec![("Content-Type", "application/wasm".to_string())],
-        )),
-        "/assets/JuliaMono-Light.woff2" => Ok(RawResponse::new(
-            font_content().into(),
-            vec![("Content-Type", "font/woff2".to_string())],
-        )),
-        _ => Err(ServiceError::NotFound),
+        path => match asset(path) {
+            Some(asset) => Ok(serve_asset(asset, headers)),
+            None => Err(ServiceError::NotFound),
+        },
+    }
+}
+
+/// serve the best encoding accepted by the client, or not modified if it already has it
+fn serve_asset<'a>(asset: &'static Asset, headers: &HeaderMap) -> RawResponse<'a> {
+    let encoding = preferred_encoding(headers);
+    let etag = asset.etag(encoding);
+    let mut response_headers = vec![
+        ("ETag", etag.clone()),
+        ("Cache-Control", asset.cache_control().to_string()),
+        ("Vary", "Accept-Encoding".to_string()),
+    ];
+
+    let not_modified = headers
+        .get(IF_NONE_MATCH)
+        .and_then(|value| value.to_str().ok())
+        .map(|value| matches_etag(value, &etag))
+        .unwrap_or(false);
+    if not_modified {
+        return RawResponse::new(vec![], response_headers).with_status(StatusCode::NOT_MODIFIED);
+    }
+
+    response_headers.push(("Content-Type", asset.content_type.to_string()));
+    match encoding {
+        Encoding::Identity => (),
+        Encoding::Gzip => response_headers.push(("Content-Encoding", "gzip".to_string())),
+        Encoding::Brotli => response_headers.push(("Content-Encoding", "br".to_string())),
+    }
+    RawResponse::from_static(asset.content(encoding), response_headers)
+}
+
+/// The coding with the highest q-value accepted by the client, brotli is preferred over gzip
+/// when they have the same. A coding which is not listed has the q-value of `*`, and `q=0`
+/// means it is not acceptable. Identity is only picked over the others when it is preferred.
+fn preferred_encoding(headers: &HeaderMap) -> Encoding {
+    let accepted: Vec<(&str, f32)> = headers
+        .get_all(ACCEPT_ENCODING)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|coding| {
+            let mut parts = coding.split(';');
+            let name = parts.next()?.trim();
+            let q = parts
+                .find_map(|param| param.trim().strip_prefix("q="))
+                .map(|q| q.trim().parse::<f32>().unwrap_or(0.0))
+                .unwrap_or(1.0);
+            Some((name, q)).filter(|(name, _)| !name.is_empty())
+        })
+        .collect();
+    let quality = |name: &str| {
+        let listed = |coding: &str| {
+            accepted
+                .iter()
+                .find(|(n, _)| n.eq_ignore_ascii_case(coding))
+        };
+        listed(name).or_else(|| listed("*")).map(|(_, q)| *q)
+    };
+    let best = [(Encoding::Brotli, "br"), (Encoding::Gzip, "gzip")]
+        .iter()
+        .filter_map(|(encoding, name)| Some((*encoding, quality(name)?)))
+        .filter(|(_, q)| *q > 0.0)
+        // the first one wins a tie
+        .fold(
+            None,
+            |best: Option<(Encoding, f32)>, (encoding, q)| match best {
+                Some((_, best_q)) if best_q >= q => best,
+                _ => Some((encoding, q)),
+            },
+        );
+    match best {
+        Some((encoding, q)) if quality("identity").map_or(true, |identity| identity <= q) => {
+            encoding
+        }
+        _ => Encoding::Identity,
+    }
+}
+
+/// If-None-Match uses the weak comparison, so `W/"tag"` matches the strong `"tag"`
+fn matches_etag(if_none_match: &str, etag: &str) -> bool {
+    if_none_match
+        .split(',')
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hyper::header::HeaderValue;
+
+    fn encoding(accept_encoding: &str) -> Encoding {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            ACCEPT_ENCODING,
+            HeaderValue::from_str(accept_encoding).unwrap(),
+        );
+        preferred_encoding(&headers)
+    }
+
+    #[test]
+    fn encoding_by_q_value() {
+        assert!(encoding("gzip, deflate, br") == Encoding::Brotli);
+        assert!(encoding("gzip") == Encoding::Gzip);
+        assert!(encoding("br;q=0.5, gzip;q=0.8") == Encoding::Gzip);
+        assert!(encoding("br;q=0, *") == Encoding::Gzip);
+        assert!(encoding("*;q=0.5, br;q=0") == Encoding::Gzip);
+        assert!(encoding("*") == Encoding::Brotli);
+        assert!(encoding("br;q=0, gzip;q=0") == Encoding::Identity);
+        assert!(encoding("identity, gzip;q=0.5") == Encoding::Identity);
+        assert!(encoding("") == Encoding::Identity);
+        assert!(preferred_encoding(&HeaderMap::new()) == Encoding::Identity);
+    }
+
+    #[test]
+    fn weak_etags_match() {
+        assert!(matches_etag("\"abc\"", "\"abc\""));
+        assert!(matches_etag("W/\"abc\"", "\"abc\""));
+        assert!(matches_etag("\"other\", W/\"abc\"", "\"abc\""));
+        assert!(matches_etag("*", "\"abc\""));
+        assert!(!matches_etag("\"abc-gz\"", "\"abc\""));
     }
 }
@@ -1,45 +1,214 @@
-use crate::serve_files;
-use client::Settings;
+use crate::ipfs::Blockstore;
+use crate::recent::RecentFiles;
+use crate::recovery::Recovery;
+use crate::serve_files::{self, Content};
+use crate::watcher;
+use crate::workspace::Workspace;
+use client::{NoteEvent, Settings};
+use futures::stream;
+use hyper::body::HttpBody;
 use hyper::header::HeaderName;
 use hyper::header::HeaderValue;
+use hyper::header::{
+    HeaderMap, ACCEPT, ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS,
+    ACCESS_CONTROL_ALLOW_METHODS, ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, HOST,
+    ORIGIN,
+};
 use hyper::{
     service::{make_service_fn, service_fn},
-    Body, Request, Response, Server, StatusCode,
+    Body, Method, Request, Response, Server, StatusCode,
 };
 use percent_encoding::percent_decode_str;
 use serde::Serialize;
+use service::ServiceRequest;
 use std::borrow::Cow;
-use std::net::SocketAddr;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
 use std::str::FromStr;
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
+use tokio::io::AsyncReadExt;
+use tokio::sync::broadcast;
 use tokio::sync::oneshot::{Receiver, Sender};
 
 const DEFAULT_IP: [u8; 4] = [127, 0, 0, 1];
 const DEFAULT_PORT: u16 = 0; //the server will choose an unused port
+/// the number of note events that are kept for clients that are lagging behind
+const EVENTS_CAPACITY: usize = 16;
+/// the largest request body that is accepted, this is for the notes and the unsaved buffers
+pub(crate) const MAX_BODY_SIZE: usize = 32 * 1024 * 1024;
+
+const CORS_HEADERS: [(HeaderName, &str); 6] = [
+    (ACCEPT, "*/*"),
+    (ACCESS_CONTROL_ALLOW_ORIGIN, "*"),
+    (ACCESS_CONTROL_EXPOSE_HEADERS, "*"),
+    (ACCESS_CONTROL_ALLOW_HEADERS, "*"),
+    (ACCESS_CONTROL_ALLOW_METHODS, "*"),
+    (ACCESS_CONTROL_ALLOW_CREDENTIALS, "true"),
+];
+
+/// the size of the chunks a file is streamed in
+const FILE_CHUNK_SIZE: usize = 64 * 1024;
+/// tells whether the request comes from the same site, sent by the browsers along with fetch
+const SEC_FETCH_SITE: &str = "sec-fetch-site";
 
+pub(crate) mod api;
+pub(crate) mod events;
+pub(crate) mod gateway;
 pub(crate) mod page;
+pub(crate) mod service;
+pub(crate) mod workspace_files;
 
-#[derive(Error, Debug, Serialize)]
+#[derive(Error, Debug)]
 pub enum ServiceError {
-    #[error("NotFound")]
+    #[error("not found")]
     NotFound,
+    #[error("bad request: {0}")]
+    BadRequest(String),
+    #[error("forbidden path: {0}")]
+    Forbidden(String),
+    /// a request which changes the workspace, sent by a page of another site
+    #[error("cross-site request from: {0}")]
+    CrossSite(String),
+    #[error("conflict: {0}")]
+    Conflict(String),
+    #[error("method not allowed")]
+    MethodNotAllowed,
+    /// the note was modified since the version the client has
+    #[error("precondition failed: {0}")]
+    PreconditionFailed(String),
+    /// the request body is larger than the limit in bytes
+    #[error("payload too large, the limit is {0} bytes")]
+    PayloadTooLarge(usize),
+    #[error("io error: {0}")]
+    IoError(String),
+    /// a bug in the server rather than a problem with the request
+    #[error("internal error: {0}")]
+    Internal(String),
+}
+
+/// The json body of an error response
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    status: u16,
+    error: &'a str,
+    message: String,
 }
 
 impl ServiceError {
-    fn status_code(&self) -> StatusCode {
+    pub(crate) fn status_code(&self) -> StatusCode {
         match self {
             ServiceError::NotFound => StatusCode::NOT_FOUND,
+            ServiceError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            ServiceError::Forbidden(_) | ServiceError::CrossSite(_) => StatusCode::FORBIDDEN,
+            ServiceError::Conflict(_) => StatusCode::CONFLICT,
+            ServiceError::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            ServiceError::PreconditionFailed(_) => StatusCode::PRECONDITION_FAILED,
+            ServiceError::PayloadTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ServiceError::IoError(_) | ServiceError::Internal(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+        }
+    }
+
+    /// a stable name of the error, so the client don't have to parse the message
+    pub(crate) fn kind(&self) -> &'static str {
+        match self {
+            ServiceError::NotFound => "not_found",
+            ServiceError::BadRequest(_) => "bad_request",
+            ServiceError::Forbidden(_) => "forbidden",
+            ServiceError::CrossSite(_) => "cross_site",
+            ServiceError::Conflict(_) => "conflict",
+            ServiceError::MethodNotAllowed => "method_not_allowed",
+            ServiceError::PreconditionFailed(_) => "precondition_failed",
+            ServiceError::PayloadTooLarge(_) => "payload_too_large",
+            ServiceError::IoError(_) => "io_error",
+            ServiceError::Internal(_) => "internal",
         }
     }
+
+    /// the body of the error response, eg: `{"status":404,"error":"not_found","message":"not found"}`
+    pub(crate) fn to_json(&self) -> String {
+        let body = ErrorBody {
+            status: self.status_code().as_u16(),
+            error: self.kind(),
+            message: self.to_string(),
+        };
+        serde_json::to_string(&body)
+            .unwrap_or_else(|_| format!(r#"{{"status":{},"error":"{}"}}"#, body.status, body.error))
+    }
 }
 
-pub(crate) fn extract_path_and_query<T>(request: &Request<T>) -> Cow<'_, str> {
-    let pnq = request
-        .uri()
-        .path_and_query()
-        .map(|pnq| pnq.as_str())
-        .unwrap_or("/");
-    percent_decode_str(pnq).decode_utf8_lossy()
+impl From<io::Error> for ServiceError {
+    fn from(e: io::Error) -> Self {
+        match e.kind() {
+            io::ErrorKind::NotFound => ServiceError::NotFound,
+            _ => ServiceError::IoError(e.to_string()),
+        }
+    }
+}
+
+/// The state shared by every request handled by the server
+#[derive(Clone)]
+pub(crate) struct State {
+    pub(crate) settings: Settings,
+    pub(crate) workspace: Workspace,
+    /// notes that are modified on disk
+    pub(crate) events: broadcast::Sender<NoteEvent>,
+    /// the local content-addressed store which stands in for ipfs
+    pub(crate) blockstore: Blockstore,
+    /// the notes recently opened or saved in this workspace
+    pub(crate) recent: Arc<Mutex<RecentFiles>>,
+    /// the snapshots of the unsaved buffers
+    pub(crate) recovery: Recovery,
+}
+
+impl State {
+    /// this also starts watching the workspace for modified notes
+    pub(crate) fn new(settings: Settings, workspace: Workspace) -> Self {
+        let events = match watcher::watch(&workspace, EVENTS_CAPACITY) {
+            Ok(events) => events,
+            Err(e) => {
+                eprintln!(
+                    "unable to watch the workspace, live reload is disabled: {}",
+                    e
+                );
+                broadcast::channel(EVENTS_CAPACITY).0
+            }
+        };
+        Self {
+            settings,
+            blockstore: Blockstore::for_workspace(&workspace),
+            recent: Arc::new(Mutex::new(RecentFiles::for_workspace(&workspace))),
+            recovery: Recovery::for_workspace(&workspace),
+            workspace,
+            events,
+        }
+    }
+
+    /// the state without the watcher, and which doesn't store anything in the config directory
+    #[cfg(test)]
+    pub(crate) fn in_memory(workspace: Workspace) -> Self {
+        Self {
+            settings: Settings::default(),
+            blockstore: Blockstore::for_workspace(&workspace),
+            recent: Arc::new(Mutex::new(RecentFiles::in_memory())),
+            recovery: Recovery::in_memory(),
+            workspace,
+            events: broadcast::channel(EVENTS_CAPACITY).0,
+        }
+    }
+}
+
+/// The percent decoded path of the request.
+/// The query is kept apart, so a `?` in the name of a note is not the start of the query.
+pub(crate) fn extract_path<T>(request: &Request<T>) -> Cow<'_, str> {
+    percent_decode_str(request.uri().path()).decode_utf8_lossy()
+}
+
+/// the query of the request without the `?`, its parameters are decoded by the endpoints
+pub(crate) fn extract_query<T>(request: &Request<T>) -> &str {
+    request.uri().query().unwrap_or_default()
 }
 
 pub fn create_response(
@@ -47,79 +216,194 @@ pub fn create_response(
     body: Body,
     headers: Vec<(&str, String)>,
 ) -> Response<Body> {
-    let mut resp = Response::builder()
-        .status(status_code)
-        .header("Accept", "*/*")
-        .header("Access-Control-Allow-Origin", "*")
-        .header("Access-Control-Expose-Headers", "*")
-        .header("Access-Control-Allow-Headers", "*")
-        .header("Access-Control-Allow-Methods", "*")
-        .header("Access-Control-Allow-Credentials", "true")
-        .body(body)
-        .expect("must build");
+    let mut resp = Response::new(body);
+    *resp.status_mut() = status_code;
     let headers_mut = resp.headers_mut();
+    for (header, value) in CORS_HEADERS.iter() {
+        headers_mut.insert(header, HeaderValue::from_static(*value));
+    }
     for (header, value) in headers.iter() {
-        headers_mut.append(
-            HeaderName::from_str(header).expect("valud header"),
-            HeaderValue::from_str(value).expect("valid value"),
-        );
+        match (HeaderName::from_str(header), HeaderValue::from_str(value)) {
+            (Ok(header), Ok(value)) => {
+                headers_mut.append(header, value);
+            }
+            _ => eprintln!("skipping invalid header {}: {:?}", header, value),
+        }
     }
     resp
 }
 
+/// The notes, the unsaved buffers, the files of the workspace and the gateway are only for the
+/// app served by this server, so the other sites the user visits can not read them.
+/// Only the app itself is public.
+fn is_private_path(path: &str) -> bool {
+    !serve_files::is_app_path(path)
+}
+
+fn remove_cors_headers(response: &mut Response<Body>) {
+    let headers_mut = response.headers_mut();
+    for (header, _) in CORS_HEADERS.iter() {
+        headers_mut.remove(header);
+    }
+}
+
+/// A request sent by a page of another site, which the browsers send even without cors
+/// such as a form post. Requests which are not sent by a browser have no `Origin`.
+fn cross_site_origin(headers: &HeaderMap) -> Option<String> {
+    let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+    match (header(ORIGIN.as_str()), header(HOST.as_str())) {
+        (Some(origin), Some(host)) => {
+            let origin_host = origin
+                .trim_start_matches("http://")
+                .trim_start_matches("https://");
+            if origin_host != host {
+                Some(origin.to_string())
+            } else {
+                None
+            }
+        }
+        (Some(origin), None) => Some(origin.to_string()),
+        (None, _) if header(SEC_FETCH_SITE) == Some("cross-site") => Some("cross-site".to_string()),
+        (None, _) => None,
+    }
+}
+
+fn is_safe_method(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
 pub fn error_response(e: ServiceError) -> Response<Body> {
-    let status_code = e.status_code();
-    let error_json = serde_json::to_string_pretty(&e).expect("must serialize");
-    create_response(status_code, error_json.into(), vec![])
+    create_response(
+        e.status_code(),
+        e.to_json().into(),
+        vec![("Content-Type", "application/json".to_string())],
+    )
+}
+
+/// read the whole request body, refusing anything larger than `MAX_BODY_SIZE`
+async fn read_body(mut body: Body) -> Result<Vec<u8>, ServiceError> {
+    let mut bytes = vec![];
+    while let Some(chunk) = body.data().await {
+        let chunk = chunk.map_err(|e| ServiceError::BadRequest(e.to_string()))?;
+        if bytes.len() + chunk.len() > MAX_BODY_SIZE {
+            return Err(ServiceError::PayloadTooLarge(MAX_BODY_SIZE));
+        }
+        bytes.extend_from_slice(&chunk);
+    }
+    Ok(bytes)
 }
 
 /// Let the server decide the port
 /// This is used by the main server
 #[allow(unused)]
 pub(crate) async fn serve_ephemeral(
-    settings: Settings,
+    state: State,
     socket_tx: Sender<SocketAddr>,
     shutdown_rx: Receiver<()>,
 ) {
-    serve(settings, None, None, socket_tx, shutdown_rx).await;
+    if let Err(e) = serve(state, None, None, socket_tx, shutdown_rx).await {
+        eprintln!("server error: {}", e);
+    }
 }
 
-fn serve_request(settings: &Settings, request: &Request<Body>) -> Response<Body> {
-    let path_and_query = extract_path_and_query(request);
-    match serve_files::raw_serve(settings, &*path_and_query) {
+async fn serve_request(state: &State, request: Request<Body>) -> Response<Body> {
+    let path = extract_path(&request).to_string();
+    let mut response = serve_request_unchecked(state, request, &path).await;
+    if is_private_path(&path) {
+        remove_cors_headers(&mut response);
+    }
+    response
+}
+
+async fn serve_request_unchecked(
+    state: &State,
+    request: Request<Body>,
+    path: &str,
+) -> Response<Body> {
+    if path == events::EVENTS_PATH {
+        return events::event_stream(state);
+    }
+    let method = request.method().clone();
+    let query = extract_query(&request).to_string();
+    let headers = request.headers().clone();
+    if !is_safe_method(&method) {
+        if let Some(origin) = cross_site_origin(&headers) {
+            return error_response(ServiceError::CrossSite(origin));
+        }
+    }
+    let body = match read_body(request.into_body()).await {
+        Ok(body) => body,
+        Err(e) => return error_response(e),
+    };
+    let result = service::handle(
+        state,
+        &ServiceRequest {
+            method,
+            path: path.to_string(),
+            query,
+            headers,
+            body,
+        },
+    );
+    match result {
         Ok(raw_response) => create_response(
-            StatusCode::OK,
-            raw_response.content.into(),
+            raw_response.status,
+            content_body(raw_response.content),
             raw_response.headers,
         ),
         Err(e) => error_response(e),
     }
 }
 
+fn content_body(content: Content) -> Body {
+    match content {
+        Content::Bytes(bytes) => bytes.into(),
+        Content::File(file, len) => {
+            let file = tokio::fs::File::from_std(file).take(len);
+            // the stream ends after an error, since the file can not be read any further
+            let chunks = stream::unfold(Some(file), |file| async move {
+                let mut file = file?;
+                let mut chunk = vec![0; FILE_CHUNK_SIZE];
+                match file.read(&mut chunk).await {
+                    Ok(0) => None,
+                    Ok(n) => {
+                        chunk.truncate(n);
+                        Some((Ok(chunk), Some(file)))
+                    }
+                    Err(e) => Some((Err(e), None)),
+                }
+            });
+            Body::wrap_stream(chunks)
+        }
+    }
+}
+
 async fn serve_request_wrap(
-    settings: Settings,
+    state: State,
     request: Request<Body>,
 ) -> Result<Response<Body>, ServiceError> {
-    Ok(serve_request(&settings, &request))
+    Ok(serve_request(&state, request).await)
 }
 
+/// This returns an error when the address can not be bound to, such as a port already in use
 pub(crate) async fn serve(
-    settings: Settings,
-    ip: Option<[u8; 4]>,
+    state: State,
+    ip: Option<IpAddr>,
     port: Option<u16>,
     socket_tx: Sender<SocketAddr>,
     shutdown_rx: Receiver<()>,
-) {
+) -> Result<(), hyper::Error> {
     let port = port.unwrap_or(DEFAULT_PORT);
-    let ip = ip.unwrap_or(DEFAULT_IP);
+    let ip = ip.unwrap_or_else(|| IpAddr::from(DEFAULT_IP));
     let socket: SocketAddr = (ip, port).into();
 
-    let server = Server::bind(&socket).serve(make_service_fn(move |_| {
-        let settings = settings.clone();
+    let builder = Server::try_bind(&socket)?;
+    let server = builder.serve(make_service_fn(move |_| {
+        let state = state.clone();
         async move {
             Ok::<_, hyper::Error>(service_fn(move |req| {
-                let settings = settings.clone();
-                serve_request_wrap(settings, req)
+                let state = state.clone();
+                serve_request_wrap(state, req)
             }))
         }
     }));
@@ -134,7 +418,161 @@ pub(crate) async fn serve(
         shutdown_rx.await.ok();
     });
 
-    if let Err(e) = graceful.await {
-        eprintln!("server error: {}", e);
+    graceful.await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::{self, File};
+    use std::io::{Seek, SeekFrom};
+    use tempfile::TempDir;
+
+    /// the content of the file outside of the workspace
+    const SECRET: &str = "the content outside of the workspace";
+
+    /// a workspace with a note, next to a secret file outside of it
+    fn state() -> (TempDir, State) {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().join("workspace");
+        fs::create_dir(&root).unwrap();
+        fs::write(root.join("note.md"), "# note").unwrap();
+        fs::write(dir.path().join("secret.md"), SECRET).unwrap();
+        let state = State::in_memory(Workspace::new(&root).unwrap());
+        (dir, state)
+    }
+
+    async fn send(state: &State, request: Request<Body>) -> (StatusCode, HeaderMap, String) {
+        let response = serve_request(state, request).await;
+        let status = response.status();
+        let headers = response.headers().clone();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        (status, headers, String::from_utf8_lossy(&body).to_string())
+    }
+
+    async fn get(state: &State, uri: &str) -> (StatusCode, HeaderMap, String) {
+        send(state, Request::get(uri).body(Body::empty()).unwrap()).await
+    }
+
+    #[tokio::test]
+    async fn paths_are_confined_to_the_workspace() {
+        let (dir, state) = state();
+        for uri in [
+            "/../secret.md",
+            "/%2e%2e/secret.md",
+            "/api/notes/../secret.md",
+            "/api/notes/..%2Fsecret.md",
+        ] {
+            let (status, _, body) = get(&state, uri).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+            assert!(!body.contains(SECRET), "{}", uri);
+        }
+        // an absolute path is relative to the workspace, where it doesn't exist
+        let secret = dir.path().join("secret.md");
+        for uri in [
+            format!("/api/notes/{}", secret.display()),
+            format!("/{}", secret.display()),
+        ] {
+            let (status, _, body) = get(&state, &uri).await;
+            assert!(status.is_client_error(), "{}", uri);
+            assert!(!body.contains(SECRET), "{}", uri);
+        }
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinks_out_of_the_workspace_are_refused() {
+        let (dir, state) = state();
+        let root = state.workspace.root().to_path_buf();
+        std::os::unix::fs::symlink(dir.path().join("secret.md"), root.join("link.md")).unwrap();
+        std::os::unix::fs::symlink(dir.path(), root.join("outside")).unwrap();
+        for uri in [
+            "/api/notes/link.md",
+            "/api/notes/outside/secret.md",
+            "/outside/secret.md",
+        ] {
+            let (status, _, body) = get(&state, uri).await;
+            assert_eq!(status, StatusCode::FORBIDDEN, "{}", uri);
+            assert!(!body.contains(SECRET), "{}", uri);
+        }
+    }
+
+    #[tokio::test]
+    async fn cors_headers_are_only_on_the_app() {
+        let (_dir, state) = state();
+        let (status, headers, _) = get(&state, "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+
+        for uri in [
+            "/api/notes/note.md",
+            "/api/notes",
+            "/api/recent",
+            "/missing.png",
+        ] {
+            let (_, headers, _) = get(&state, uri).await;
+            for (header, _) in CORS_HEADERS.iter() {
+                assert!(!headers.contains_key(header), "{} {}", uri, header);
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn cross_site_writes_are_refused() {
+        let (_dir, state) = state();
+        let note = state.workspace.root().join("note.md");
+        let put = |origin: &str| {
+            Request::put("/api/notes/note.md")
+                .header(HOST, "localhost:8080")
+                .header(ORIGIN, origin)
+                .body(Body::from("# edited"))
+                .unwrap()
+        };
+
+        let (status, _, _) = send(&state, put("http://evil.example")).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let post = Request::post("/api/notes/new.md")
+            .header(SEC_FETCH_SITE, "cross-site")
+            .body(Body::from("# new"))
+            .unwrap();
+        let (status, _, _) = send(&state, post).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(fs::read_to_string(&note).unwrap(), "# note");
+        assert!(!state.workspace.root().join("new.md").exists());
+
+        let (status, _, _) = send(&state, put("http://localhost:8080")).await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert_eq!(fs::read_to_string(&note).unwrap(), "# edited");
+    }
+
+    #[test]
+    fn only_the_app_is_public() {
+        assert!(!is_private_path("/"));
+        assert!(is_private_path("/api/notes/note.md"));
+        assert!(is_private_path("/ipfs/bafkqaaa"));
+        assert!(is_private_path("/image.png"));
+    }
+
+    #[test]
+    fn encoded_query_is_part_of_the_path() {
+        let request = Request::get("/api/notes/what%3F%20%2523.md?open")
+            .body(())
+            .unwrap();
+        assert_eq!(extract_path(&request), "/api/notes/what? %23.md");
+        assert_eq!(extract_query(&request), "open");
+    }
+
+    #[tokio::test]
+    async fn files_are_streamed_from_their_position() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("data.bin");
+        let content: Vec<u8> = (0..3 * FILE_CHUNK_SIZE).map(|i| i as u8).collect();
+        fs::write(&path, &content).unwrap();
+        let mut file = File::open(&path).unwrap();
+        file.seek(SeekFrom::Start(10)).unwrap();
+
+        let body = content_body(Content::File(file, 2 * FILE_CHUNK_SIZE as u64));
+        let bytes = hyper::body::to_bytes(body).await.unwrap();
+        assert_eq!(&bytes[..], &content[10..10 + 2 * FILE_CHUNK_SIZE]);
     }
 }
@@ -0,0 +1,307 @@
+//! REST endpoints for the notes in the workspace
+//!
+//! - `GET /api/notes` list all the notes in the workspace
+//! - `GET /api/notes/<path>` read the content of the note, its version is in the `ETag` and
+//!    `X-Modified` headers
+//! - `GET /api/notes/<path>?open` read the note which is opened in the app, which adds it to
+//!    the recent notes
+//! - `HEAD /api/notes/<path>` the version of the note, without its content
+//! - `POST /api/notes/<path>` create a new note, fails if it already exists
+//! - `PUT /api/notes/<path>` write the content of the note, with `If-Match` it fails with
+//!    `412 Precondition Failed` when the note was modified by another program
+//! - `DELETE /api/notes/<path>` delete the note
+//! - `POST /api/publish/<path>` publish the note together with the files it refers to
+//! - `GET /api/recent` list the notes recently opened or saved, the most recent first
+//! - `DELETE /api/recent` clear the list of recent notes
+//! - `GET /api/recovery` list the snapshots of the unsaved buffers, the most recent first
+//! - `PUT /api/recovery/<session>/<note>` store the snapshot of the unsaved buffer of the note
+//!    taken in this session of the app, without a note it is the buffer which is not saved to a
+//!    file yet
+//! - `DELETE /api/recovery/<session>/<note>` remove the snapshot of the note taken in the session
+use crate::ipfs::Blockstore;
+use crate::publish;
+use crate::recent::RecentFiles;
+use crate::recovery::{self, Recovery};
+use crate::serve_files::RawResponse;
+use crate::server::ServiceError;
+use crate::workspace::Workspace;
+use client::{NoteVersion, MODIFIED_HEADER};
+use hyper::header::{HeaderMap, IF_MATCH};
+use hyper::{Method, StatusCode};
+use std::sync::{Mutex, MutexGuard};
+
+pub(crate) const NOTES_PREFIX: &str = "/api/notes";
+pub(crate) const PUBLISH_PREFIX: &str = "/api/publish";
+pub(crate) const RECENT_PATH: &str = "/api/recent";
+pub(crate) const RECOVERY_PREFIX: &str = "/api/recovery";
+/// the query of a note which is opened, rather than only read to check if it was modified
+const OPEN_QUERY: &str = "open";
+
+pub(crate) fn is_notes_path(path: &str) -> bool {
+    has_prefix(path, NOTES_PREFIX)
+}
+
+pub(crate) fn is_publish_path(path: &str) -> bool {
+    has_prefix(path, PUBLISH_PREFIX)
+}
+
+pub(crate) fn is_recent_path(path: &str) -> bool {
+    path == RECENT_PATH
+}
+
+pub(crate) fn is_recovery_path(path: &str) -> bool {
+    has_prefix(path, RECOVERY_PREFIX)
+}
+
+pub(crate) fn serve_notes<'a>(
+    workspace: &Workspace,
+    recent: &Mutex<RecentFiles>,
+    method: &Method,
+    path: &str,
+    query: &str,
+    headers: &HeaderMap,
+    body: &[u8],
+) -> Result<RawResponse<'a>, ServiceError> {
+    let note = path
+        .trim_start_matches(NOTES_PREFIX)
+        .trim_start_matches('/');
+
+    if note.is_empty() {
+        return match *method {
+            Method::GET => json_response(&workspace.list_notes()?),
+            _ => Err(ServiceError::MethodNotAllowed),
+        };
+    }
+
+    match *method {
+        Method::GET => {
+            let content = workspace.read_note(note)?;
+            let mut headers = version_headers(&workspace.note_version(note)?);
+            headers.push(("Content-Type", "text/markdown; charset=UTF-8".to_string()));
+            if has_query(query, OPEN_QUERY) {
+                lock(recent)?.add(note);
+            }
+            Ok(RawResponse::new(content.into_bytes(), headers))
+        }
+        // the version of the note, to check if it was modified without reading it
+        Method::HEAD => Ok(RawResponse::new(
+            vec![],
+            version_headers(&workspace.note_version(note)?),
+        )),
+        Method::POST => {
+            workspace.create_note(note, &body_to_string(body)?)?;
+            lock(recent)?.add(note);
+            Ok(
+                RawResponse::new(vec![], version_headers(&workspace.note_version(note)?))
+                    .with_status(StatusCode::CREATED),
+            )
+        }
+        // with `If-Match`, the note is only written if it was not modified by another program
+        Method::PUT => {
+            let content = body_to_string(body)?;
+            match expected_version(headers) {
+                Some(expected) => workspace.write_note_if(note, &content, &expected)?,
+                None => workspace.write_note(note, &content)?,
+            }
+            lock(recent)?.add(note);
+            Ok(
+                RawResponse::new(vec![], version_headers(&workspace.note_version(note)?))
+                    .with_status(StatusCode::NO_CONTENT),
+            )
+        }
+        Method::DELETE => {
+            workspace.delete_note(note)?;
+            lock(recent)?.remove(note);
+            Ok(RawResponse::new(vec![], vec![]).with_status(StatusCode::NO_CONTENT))
+        }
+        _ => Err(ServiceError::MethodNotAllowed),
+    }
+}
+
+pub(crate) fn serve_publish<'a>(
+    workspace: &Workspace,
+    blockstore: &Blockstore,
+    method: &Method,
+    path: &str,
+) -> Result<RawResponse<'a>, ServiceError> {
+    let note = path
+        .trim_start_matches(PUBLISH_PREFIX)
+        .trim_start_matches('/');
+    match *method {
+        Method::POST if !note.is_empty() => {
+            json_response(&publish::publish(workspace, blockstore, note)?)
+        }
+        _ => Err(ServiceError::MethodNotAllowed),
+    }
+}
+
+/// the notes which no longer exist in the workspace are pruned from the list
+pub(crate) fn serve_recent<'a>(
+    workspace: &Workspace,
+    recent: &Mutex<RecentFiles>,
+    method: &Method,
+) -> Result<RawResponse<'a>, ServiceError> {
+    match *method {
+        Method::GET => {
+            let paths = lock(recent)?.list(|note| {
+                workspace
+                    .resolve(note)
+                    .map(|path| path.is_file())
+                    .unwrap_or(false)
+            });
+            json_response(&paths)
+        }
+        Method::DELETE => {
+            lock(recent)?.clear();
+            Ok(RawResponse::new(vec![], vec![]).with_status(StatusCode::NO_CONTENT))
+        }
+        _ => Err(ServiceError::MethodNotAllowed),
+    }
+}
+
+pub(crate) fn serve_recovery<'a>(
+    recovery: &Recovery,
+    method: &Method,
+    path: &str,
+    body: &[u8],
+) -> Result<RawResponse<'a>, ServiceError> {
+    let path = path.trim_start_matches(RECOVERY_PREFIX);
+    let path = path.strip_prefix('/').unwrap_or_default();
+    // the notes of the desktop app are absolute paths, so only the separator is removed
+    let (session, note) = match path.split_once('/') {
+        Some((session, note)) => (session, Some(note).filter(|note| !note.is_empty())),
+        None => (path, None),
+    };
+    match *method {
+        Method::GET if path.is_empty() => json_response(&recovery.list()?),
+        Method::PUT | Method::DELETE if !recovery::is_session(session) => Err(
+            ServiceError::BadRequest(format!("invalid session: {:?}", session)),
+        ),
+        Method::PUT => {
+            recovery.store(session, note, &body_to_string(body)?)?;
+            Ok(RawResponse::new(vec![], vec![]).with_status(StatusCode::NO_CONTENT))
+        }
+        Method::DELETE => {
+            recovery.remove(session, note)?;
+            Ok(RawResponse::new(vec![], vec![]).with_status(StatusCode::NO_CONTENT))
+        }
+        _ => Err(ServiceError::MethodNotAllowed),
+    }
+}
+
+/// the `ETag` and `X-Modified` headers of the note
+fn version_headers<'a>(version: &NoteVersion) -> Vec<(&'a str, String)> {
+    vec![
+        ("ETag", version.etag()),
+        (MODIFIED_HEADER, version.modified.to_string()),
+    ]
+}
+
+/// the version the client has, from the `If-Match` and `X-Modified` headers of the request
+fn expected_version(headers: &HeaderMap) -> Option<NoteVersion> {
+    let etag = headers.get(IF_MATCH)?.to_str().ok()?;
+    let modified = headers
+        .get(MODIFIED_HEADER)
+        .and_then(|modified| modified.to_str().ok())
+        .unwrap_or("0");
+    NoteVersion::from_headers(etag, modified)
+}
+
+fn lock(recent: &Mutex<RecentFiles>) -> Result<MutexGuard<RecentFiles>, ServiceError> {
+    recent
+        .lock()
+        .map_err(|_| ServiceError::Internal("the recent files lock is poisoned".to_string()))
+}
+
+pub(crate) fn json_response<'a, T: serde::Serialize>(
+    value: &T,
+) -> Result<RawResponse<'a>, ServiceError> {
+    let json = serde_json::to_string(value).map_err(|e| ServiceError::Internal(e.to_string()))?;
+    Ok(RawResponse::new(
+        json.into_bytes(),
+        vec![("Content-Type", "application/json".to_string())],
+    ))
+}
+
+fn body_to_string(body: &[u8]) -> Result<String, ServiceError> {
+    String::from_utf8(body.to_vec()).map_err(|e| ServiceError::BadRequest(e.to_string()))
+}
+
+fn has_prefix(path: &str, prefix: &str) -> bool {
+    path == prefix || path.starts_with(&format!("{}/", prefix))
+}
+
+/// the query has this parameter, with or without a value
+fn has_query(query: &str, name: &str) -> bool {
+    query
+        .split('&')
+        .any(|param| param.split('=').next() == Some(name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn workspace() -> (TempDir, Workspace) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("note.md"), "# note").unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        (dir, workspace)
+    }
+
+    fn request(
+        workspace: &Workspace,
+        recent: &Mutex<RecentFiles>,
+        method: Method,
+        path_and_query: &str,
+    ) -> RawResponse<'static> {
+        let (path, query) = path_and_query
+            .split_once('?')
+            .unwrap_or((path_and_query, ""));
+        serve_notes(
+            workspace,
+            recent,
+            &method,
+            path,
+            query,
+            &HeaderMap::new(),
+            b"# edited",
+        )
+        .unwrap()
+    }
+
+    fn recent_notes(recent: &Mutex<RecentFiles>) -> Vec<String> {
+        recent.lock().unwrap().list(|_| true)
+    }
+
+    #[test]
+    fn reading_a_note_does_not_add_it_to_the_recent_notes() {
+        let (_dir, workspace) = workspace();
+        let recent = Mutex::new(RecentFiles::in_memory());
+        request(&workspace, &recent, Method::GET, "/api/notes/note.md");
+        request(&workspace, &recent, Method::HEAD, "/api/notes/note.md");
+        assert!(recent_notes(&recent).is_empty());
+    }
+
+    #[test]
+    fn opened_and_saved_notes_are_recent() {
+        let (_dir, workspace) = workspace();
+        let recent = Mutex::new(RecentFiles::in_memory());
+        request(&workspace, &recent, Method::GET, "/api/notes/note.md?open");
+        assert_eq!(recent_notes(&recent), vec!["note.md"]);
+
+        request(&workspace, &recent, Method::POST, "/api/notes/new.md");
+        request(&workspace, &recent, Method::PUT, "/api/notes/other.md");
+        assert_eq!(recent_notes(&recent), vec!["other.md", "new.md", "note.md"]);
+    }
+
+    #[test]
+    fn query_parameters() {
+        assert!(has_query("open", "open"));
+        assert!(has_query("a=1&open=1", "open"));
+        assert!(!has_query("", "open"));
+        assert!(!has_query("opened", "open"));
+    }
+}
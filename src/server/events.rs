@@ -0,0 +1,39 @@
+//! Server-Sent Events endpoint which pushes the notes modified on disk to the browser
+use crate::server::{create_response, State};
+use futures::stream;
+use hyper::{Body, Response, StatusCode};
+use std::convert::Infallible;
+use tokio::sync::broadcast::error::RecvError;
+
+pub(crate) const EVENTS_PATH: &str = "/api/events";
+
+/// a never ending response which sends each note event as a json in the `data` field
+pub(crate) fn event_stream(state: &State) -> Response<Body> {
+    let events_rx = state.events.subscribe();
+    let stream = stream::unfold(events_rx, |mut events_rx| async move {
+        loop {
+            match events_rx.recv().await {
+                Ok(event) => match serde_json::to_string(&event) {
+                    Ok(json) => {
+                        return Some((
+                            Ok::<_, Infallible>(format!("data: {}\n\n", json)),
+                            events_rx,
+                        ))
+                    }
+                    Err(e) => eprintln!("unable to serialize the note event: {}", e),
+                },
+                // the client is too slow, skip the missed events
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    create_response(
+        StatusCode::OK,
+        Body::wrap_stream(stream),
+        vec![
+            ("Content-Type", "text/event-stream".to_string()),
+            ("Cache-Control", "no-cache".to_string()),
+        ],
+    )
+}
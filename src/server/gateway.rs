@@ -0,0 +1,154 @@
+//! A gateway to the local blockstore, with the same routes as an IPFS http gateway
+//!
+//! - `GET /ipfs/<cid>?filename=<name>` read the content of the file, the name is only used
+//!    for the download and to show the images, audio, video and plain text inline
+//!
+//! The files are only added to the blockstore by publishing a note.
+use crate::ipfs::{Blockstore, Cid, InvalidCid};
+use crate::serve_files::RawResponse;
+use crate::server::workspace_files;
+use crate::server::ServiceError;
+use hyper::Method;
+use percent_encoding::{percent_decode_str, utf8_percent_encode, NON_ALPHANUMERIC};
+use std::path::Path;
+
+pub(crate) const IPFS_PREFIX: &str = "/ipfs";
+
+/// The url of a file in the gateway.
+/// The file name is passed along, the same way as the ipfs gateways do.
+pub(crate) fn file_url(cid: &str, path: &str) -> String {
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    format!(
+        "{}/{}?filename={}",
+        IPFS_PREFIX,
+        cid,
+        utf8_percent_encode(&file_name, NON_ALPHANUMERIC)
+    )
+}
+
+pub(crate) fn is_gateway_path(path: &str) -> bool {
+    path == IPFS_PREFIX || path.starts_with(&format!("{}/", IPFS_PREFIX))
+}
+
+pub(crate) fn serve_gateway<'a>(
+    blockstore: &Blockstore,
+    method: &Method,
+    path: &str,
+    query: &str,
+) -> Result<RawResponse<'a>, ServiceError> {
+    let path = path.trim_start_matches(IPFS_PREFIX).trim_start_matches('/');
+    let file_name = query
+        .split('&')
+        .find_map(|param| param.strip_prefix("filename="))
+        .map(|file_name| percent_decode_str(file_name).decode_utf8_lossy())
+        .filter(|file_name| !file_name.is_empty());
+
+    match *method {
+        Method::GET if !path.is_empty() => {
+            // only a single file is addressed, there are no directories in the blockstore
+            let cid: Cid = path
+                .trim_end_matches('/')
+                .parse()
+                .map_err(|e: InvalidCid| ServiceError::BadRequest(e.to_string()))?;
+            let content = blockstore.cat(&cid)?;
+            let mut headers = content_headers(file_name.as_deref());
+            // the content never changes for the same cid
+            headers.push((
+                "Cache-Control",
+                "public, max-age=31536000, immutable".to_string(),
+            ));
+            headers.push(("ETag", format!("\"{}\"", cid)));
+            Ok(RawResponse::new(content, headers))
+        }
+        _ => Err(ServiceError::MethodNotAllowed),
+    }
+}
+
+/// The file name comes from the url, so it can't be trusted to pick how the content is handled.
+/// Only the passive content such as images, audio, video and plain text are shown inline, the
+/// rest is downloaded. The content is never sniffed, and it is sandboxed in any case.
+fn content_headers<'a>(file_name: Option<&str>) -> Vec<(&'a str, String)> {
+    let content_type = file_name
+        .map(|file_name| workspace_files::content_type(Path::new(file_name)))
+        .filter(|content_type| is_passive(content_type));
+    let disposition = if content_type.is_some() {
+        "inline"
+    } else {
+        "attachment"
+    };
+    let disposition = match file_name {
+        Some(file_name) => format!(
+            "{}; filename*=UTF-8''{}",
+            disposition,
+            utf8_percent_encode(file_name, NON_ALPHANUMERIC)
+        ),
+        None => disposition.to_string(),
+    };
+    vec![
+        (
+            "Content-Type",
+            content_type.unwrap_or_else(|| "application/octet-stream".to_string()),
+        ),
+        ("Content-Disposition", disposition),
+        ("X-Content-Type-Options", "nosniff".to_string()),
+        ("Content-Security-Policy", "sandbox".to_string()),
+    ]
+}
+
+/// content which can't run scripts, svg is an image which can
+fn is_passive(content_type: &str) -> bool {
+    let (kind, subtype) = content_type.split_once('/').unwrap_or((content_type, ""));
+    match kind {
+        "image" => !subtype.starts_with("svg"),
+        "audio" | "video" => true,
+        "text" => subtype == "plain",
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header<'a>(headers: &'a [(&str, String)], name: &str) -> Option<&'a str> {
+        headers
+            .iter()
+            .find(|(header, _)| *header == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn passive_content_is_shown() {
+        let headers = content_headers(Some("cat photo.png"));
+        assert_eq!(header(&headers, "Content-Type"), Some("image/png"));
+        assert_eq!(
+            header(&headers, "Content-Disposition"),
+            Some("inline; filename*=UTF-8''cat%20photo%2Epng")
+        );
+        let headers = content_headers(Some("data.txt"));
+        assert_eq!(header(&headers, "Content-Type"), Some("text/plain"));
+    }
+
+    #[test]
+    fn active_content_is_downloaded() {
+        for file_name in ["page.html", "drawing.svg", "script.js", "note.md"].iter() {
+            let headers = content_headers(Some(file_name));
+            assert_eq!(
+                header(&headers, "Content-Type"),
+                Some("application/octet-stream"),
+                "{}",
+                file_name
+            );
+            assert!(header(&headers, "Content-Disposition")
+                .unwrap()
+                .starts_with("attachment;"));
+            assert_eq!(header(&headers, "X-Content-Type-Options"), Some("nosniff"));
+            assert_eq!(header(&headers, "Content-Security-Policy"), Some("sandbox"));
+        }
+        let headers = content_headers(None);
+        assert_eq!(header(&headers, "Content-Disposition"), Some("attachment"));
+    }
+}
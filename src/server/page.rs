@@ -7,6 +7,8 @@ use sauron::html::tags;
 use sauron::jss::jss;
 use sauron::prelude::*;
 
+const FONT_URL: &str = "./assets/JuliaMono-Light.woff2";
+
 pub(crate) fn index(settings: &Settings) -> Node<()> {
     #[cfg(feature = "open-ports")]
     let base_url = "import.meta.url";
@@ -33,7 +35,7 @@ pub(crate) fn index(settings: &Settings) -> Node<()> {
                         [],
                     ),
                     tags::title([], [text(settings.app_title.to_string())]),
-                    tags::style([r#type("text/css")], [text(css())]),
+                    tags::style([r#type("text/css")], [text(css(FONT_URL))]),
                     link(
                         [
                             attributes::rel("modulepreload"),
@@ -64,7 +66,14 @@ pub(crate) fn index(settings: &Settings) -> Node<()> {
                 [],
                 [
                     main(
-                        [id(&settings.app_container.to_string())],
+                        [
+                            id(&settings.app_container.to_string()),
+                            if let Some(open_note) = &settings.open_note {
+                                attr(client::OPEN_NOTE_ATTRIBUTE, open_note.to_string())
+                            } else {
+                                empty_attr()
+                            },
+                        ],
                         [div([class("preload_spinner")], [])],
                     ),
                     script(
@@ -81,11 +90,121 @@ pub(crate) fn index(settings: &Settings) -> Node<()> {
     )
 }
 
-fn css() -> String {
+/// A self-contained page of an already rendered markdown, it doesn't need the wasm client.
+/// The font is embedded in the page.
+/// This is used by the generate_html binary
+#[allow(unused)]
+pub(crate) fn static_page(title: &str, rendered: Node<()>) -> Node<()> {
+    let font_url = format!(
+        "data:font/woff2;base64,{}",
+        base64::encode(serve_files::font_content())
+    );
+    static_document(
+        title,
+        &font_url,
+        main(
+            [class("rendered_markdown")],
+            [div([class("padded")], [rendered])],
+        ),
+    )
+}
+
+/// A page of the static site, with the navigation sidebar on the left of the rendered markdown.
+/// The `root_prefix` is the relative path from this page back to the root of the site.
+/// This is used by the generate_html binary
+#[allow(unused)]
+pub(crate) fn site_page(
+    title: &str,
+    root_prefix: &str,
+    sidebar: Node<()>,
+    rendered: Node<()>,
+) -> Node<()> {
+    let font_url = format!("{}{}", root_prefix, FONT_URL.trim_start_matches("./"));
+    static_document(
+        title,
+        &font_url,
+        div(
+            [class("site")],
+            [
+                nav([class("sidebar")], [sidebar]),
+                main(
+                    [class("rendered_markdown")],
+                    [div([class("padded")], [rendered])],
+                ),
+            ],
+        ),
+    )
+}
+
+fn static_document(title: &str, font_url: &str, content_body: Node<()>) -> Node<()> {
+    html(
+        [],
+        [
+            head(
+                [],
+                [
+                    meta([content("text/html;charset=utf-8")], []),
+                    meta(
+                        [
+                            attributes::name("viewport"),
+                            content("width=device-width, initial-scale=1"),
+                        ],
+                        [],
+                    ),
+                    tags::title([], [text(title)]),
+                    tags::style([r#type("text/css")], [text(css(font_url))]),
+                    tags::style([r#type("text/css")], [text(static_css())]),
+                    tags::style([r#type("text/css")], [text(client::markdown_style())]),
+                ],
+            ),
+            body([], [content_body]),
+        ],
+    )
+}
+
+/// the styles of the rendered markdown in a static page, similar to the one in the app
+fn static_css() -> String {
+    jss! {
+        "body": {
+            background_color: "#fff",
+        },
+
+        ".rendered_markdown .padded": {
+            padding: px(10),
+        },
+
+        ".site": {
+            display: "flex",
+        },
+
+        ".site .sidebar": {
+            flex: "none",
+            width: px(250),
+            min_height: "100vh",
+            padding: px(10),
+            box_sizing: "border-box",
+            background_color: "#eee",
+        },
+
+        ".site .sidebar ul": {
+            padding_left: px(15),
+        },
+
+        ".site .sidebar a.active": {
+            font_weight: "bold",
+        },
+
+        ".site .rendered_markdown": {
+            flex: 1,
+        },
+    }
+}
+
+fn css(font_url: &str) -> String {
     jss! {
         "@font-face": {
           font_family: "JuliaMono",
-          src: "url(./assets/JuliaMono-Light.woff2)",
+          src: format!("url({})", font_url),
         },
 
         "body": {
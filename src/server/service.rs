@@ -0,0 +1,63 @@
+//! The request handler shared by the http server and the webview bridge of the desktop app.
+//!
+//! It only deals with requests that are fully read, so every endpoint added here works the
+//! same regardless of how the request arrived.
+use crate::serve_files::{self, RawResponse};
+use crate::server::{api, gateway, workspace_files, ServiceError, State, MAX_BODY_SIZE};
+use hyper::header::HeaderMap;
+use hyper::Method;
+
+/// A request which body is already read
+#[derive(Debug)]
+pub(crate) struct ServiceRequest {
+    pub(crate) method: Method,
+    /// the percent decoded path
+    pub(crate) path: String,
+    /// the query without the `?`, which is not decoded
+    pub(crate) query: String,
+    pub(crate) headers: HeaderMap,
+    pub(crate) body: Vec<u8>,
+}
+
+pub(crate) fn handle<'a>(
+    state: &State,
+    request: &ServiceRequest,
+) -> Result<RawResponse<'a>, ServiceError> {
+    let ServiceRequest {
+        method,
+        path,
+        query,
+        headers,
+        body,
+    } = request;
+    if body.len() > MAX_BODY_SIZE {
+        return Err(ServiceError::PayloadTooLarge(MAX_BODY_SIZE));
+    }
+    if api::is_notes_path(path) {
+        api::serve_notes(
+            &state.workspace,
+            &state.recent,
+            method,
+            path,
+            query,
+            headers,
+            body,
+        )
+    } else if api::is_recent_path(path) {
+        api::serve_recent(&state.workspace, &state.recent, method)
+    } else if api::is_recovery_path(path) {
+        api::serve_recovery(&state.recovery, method, path, body)
+    } else if api::is_publish_path(path) {
+        api::serve_publish(&state.workspace, &state.blockstore, method, path)
+    } else if gateway::is_gateway_path(path) {
+        gateway::serve_gateway(&state.blockstore, method, path, query)
+    } else {
+        match serve_files::raw_serve(&state.settings, path, headers) {
+            // the files referenced by the notes such as images
+            Err(ServiceError::NotFound) => {
+                workspace_files::serve(&state.workspace, method, path, headers)
+            }
+            result => result,
+        }
+    }
+}
@@ -0,0 +1,254 @@
+//! Serve the files in the workspace which are referenced by the notes, such as images and data
+//! files. Only a single byte range is supported in `Range` requests, which is what browsers use
+//! when playing audio and video. The whole file is sent for the other ranges.
+//!
+//! The notes are not served here, they are only read through the api. The files are written by
+//! the user, so an html or svg file is sandboxed instead of running as a page of the app.
+use crate::serve_files::RawResponse;
+use crate::server::ServiceError;
+use crate::workspace::{self, Workspace};
+use hyper::header::{HeaderMap, RANGE};
+use hyper::{Method, StatusCode};
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+use std::path::Path;
+
+pub(crate) fn serve<'a>(
+    workspace: &Workspace,
+    method: &Method,
+    relative: &str,
+    headers: &HeaderMap,
+) -> Result<RawResponse<'a>, ServiceError> {
+    if *method != Method::GET && *method != Method::HEAD {
+        return Err(ServiceError::MethodNotAllowed);
+    }
+    if workspace::is_hidden(relative) {
+        return Err(ServiceError::Forbidden(relative.to_string()));
+    }
+    let path = workspace.resolve(relative)?;
+    if workspace::is_note(&path) {
+        return Err(ServiceError::Forbidden(relative.to_string()));
+    }
+    if !path.is_file() {
+        return Err(ServiceError::NotFound);
+    }
+
+    let mut file = File::open(&path)?;
+    let total = file.metadata()?.len();
+    let mut response_headers = file_headers(content_type(&path));
+    let range = headers
+        .get(RANGE)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| parse_range(value, total));
+
+    match range {
+        Some(ByteRange::Single(start, end)) => {
+            file.seek(SeekFrom::Start(start))?;
+            response_headers.push((
+                "Content-Range",
+                format!("bytes {}-{}/{}", start, end, total),
+            ));
+            Ok(
+                RawResponse::from_file(file, end - start + 1, response_headers)
+                    .with_status(StatusCode::PARTIAL_CONTENT),
+            )
+        }
+        Some(ByteRange::Unsatisfiable) => {
+            response_headers.push(("Content-Range", format!("bytes */{}", total)));
+            Ok(RawResponse::new(vec![], response_headers)
+                .with_status(StatusCode::RANGE_NOT_SATISFIABLE))
+        }
+        None => Ok(RawResponse::from_file(file, total, response_headers)),
+    }
+}
+
+/// The content type is not sniffed by the browser, and a page such as an html or svg file is
+/// sandboxed so its scripts don't run with the origin of the app.
+fn file_headers<'a>(content_type: String) -> Vec<(&'a str, String)> {
+    vec![
+        ("Content-Type", content_type),
+        ("Accept-Ranges", "bytes".to_string()),
+        ("X-Content-Type-Options", "nosniff".to_string()),
+        ("Content-Security-Policy", "sandbox".to_string()),
+    ]
+}
+
+/// guess the content type from the file extension
+pub(crate) fn content_type(path: &Path) -> String {
+    mime_guess::from_path(path)
+        .first_or_octet_stream()
+        .to_string()
+}
+
+#[derive(Debug, PartialEq)]
+enum ByteRange {
+    /// the inclusive start and end
+    Single(u64, u64),
+    Unsatisfiable,
+}
+
+/// Parse a single range such as `bytes=0-499`, `bytes=500-` or `bytes=-500`.
+/// None when the header is ignored, such as multiple ranges or an invalid range,
+/// in which case the whole file is sent as allowed by RFC 7233.
+fn parse_range(value: &str, total: u64) -> Option<ByteRange> {
+    let range = value.trim().strip_prefix("bytes=")?;
+    // multiple ranges are not supported
+    if range.contains(',') {
+        return None;
+    }
+    let (start, end) = range.split_once('-')?;
+    let (start, end): (u64, Option<u64>) = match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let suffix: u64 = suffix.parse().ok()?;
+            if suffix == 0 || total == 0 {
+                return Some(ByteRange::Unsatisfiable);
+            }
+            (total.saturating_sub(suffix), None)
+        }
+        (start, "") => (start.parse().ok()?, None),
+        (start, end) => {
+            let (start, end) = (start.parse().ok()?, end.parse().ok()?);
+            if start > end {
+                return None;
+            }
+            (start, Some(end))
+        }
+    };
+    if start >= total {
+        return Some(ByteRange::Unsatisfiable);
+    }
+    let end = end.unwrap_or(total - 1).min(total - 1);
+    Some(ByteRange::Single(start, end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn workspace() -> (TempDir, Workspace) {
+        let dir = tempfile::tempdir().unwrap();
+        fs::write(dir.path().join("image.png"), "0123456789").unwrap();
+        fs::write(dir.path().join("page.html"), "<script>alert(1)</script>").unwrap();
+        fs::write(dir.path().join("note.md"), "# note").unwrap();
+        fs::create_dir(dir.path().join(".madnotes")).unwrap();
+        fs::write(dir.path().join(".madnotes/recent.json"), "[]").unwrap();
+        let workspace = Workspace::new(dir.path()).unwrap();
+        (dir, workspace)
+    }
+
+    fn get(
+        workspace: &Workspace,
+        path: &str,
+        range: Option<&str>,
+    ) -> Result<RawResponse<'static>, ServiceError> {
+        let mut headers = HeaderMap::new();
+        if let Some(range) = range {
+            headers.insert(RANGE, range.parse().unwrap());
+        }
+        serve(workspace, &Method::GET, path, &headers)
+    }
+
+    fn header<'a>(response: &'a RawResponse, name: &str) -> Option<&'a str> {
+        response
+            .headers
+            .iter()
+            .find(|(header, _)| header.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    #[test]
+    fn whole_file() {
+        let (_dir, workspace) = workspace();
+        let response = get(&workspace, "/image.png", None).unwrap();
+        assert_eq!(response.status, StatusCode::OK);
+        assert_eq!(header(&response, "Content-Type"), Some("image/png"));
+        assert_eq!(response.content.into_bytes().unwrap(), b"0123456789");
+    }
+
+    #[test]
+    fn part_of_the_file() {
+        let (_dir, workspace) = workspace();
+        let response = get(&workspace, "/image.png", Some("bytes=2-5")).unwrap();
+        assert_eq!(response.status, StatusCode::PARTIAL_CONTENT);
+        assert_eq!(header(&response, "Content-Range"), Some("bytes 2-5/10"));
+        assert_eq!(response.content.into_bytes().unwrap(), b"2345");
+
+        let response = get(&workspace, "/image.png", Some("bytes=10-")).unwrap();
+        assert_eq!(response.status, StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(header(&response, "Content-Range"), Some("bytes */10"));
+    }
+
+    #[test]
+    fn files_are_not_sniffed_and_sandboxed() {
+        let (_dir, workspace) = workspace();
+        for range in [None, Some("bytes=0-1"), Some("bytes=20-")].iter() {
+            let response = get(&workspace, "/page.html", *range).unwrap();
+            assert_eq!(header(&response, "X-Content-Type-Options"), Some("nosniff"));
+            assert_eq!(
+                header(&response, "Content-Security-Policy"),
+                Some("sandbox")
+            );
+        }
+    }
+
+    #[test]
+    fn notes_and_hidden_files_are_refused() {
+        let (_dir, workspace) = workspace();
+        for path in [
+            "/note.md",
+            "/NOTE.MD",
+            "/.madnotes/recent.json",
+            "/../image.png",
+        ]
+        .iter()
+        {
+            assert!(
+                matches!(get(&workspace, path, None), Err(ServiceError::Forbidden(_))),
+                "{}",
+                path
+            );
+        }
+        assert!(matches!(
+            get(&workspace, "/missing.png", None),
+            Err(ServiceError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn single_ranges() {
+        assert_eq!(
+            parse_range("bytes=0-499", 1000),
+            Some(ByteRange::Single(0, 499))
+        );
+        assert_eq!(
+            parse_range("bytes=500-", 1000),
+            Some(ByteRange::Single(500, 999))
+        );
+        assert_eq!(
+            parse_range("bytes=-500", 1000),
+            Some(ByteRange::Single(500, 999))
+        );
+        assert_eq!(
+            parse_range("bytes=900-2000", 1000),
+            Some(ByteRange::Single(900, 999))
+        );
+    }
+
+    #[test]
+    fn unsatisfiable_range() {
+        assert_eq!(
+            parse_range("bytes=1000-", 1000),
+            Some(ByteRange::Unsatisfiable)
+        );
+        assert_eq!(parse_range("bytes=0-10", 0), Some(ByteRange::Unsatisfiable));
+    }
+
+    #[test]
+    fn ignored_ranges_send_the_whole_file() {
+        assert_eq!(parse_range("bytes=0-1,5-6", 1000), None);
+        assert_eq!(parse_range("bytes=5-1", 1000), None);
+        assert_eq!(parse_range("items=0-1", 1000), None);
+    }
+}
@@ -0,0 +1,84 @@
+//! The versions of the notes on disk, so the changes made by other programs can be detected
+use client::NoteVersion;
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// The version check and the write are done while holding this lock, so two saves of the same
+/// version, such as one from the server and one from the desktop app, can't both pass the check.
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// the modification time and the hash of the content of the file
+pub(crate) fn note_version(path: &Path) -> io::Result<NoteVersion> {
+    let modified = fs::metadata(path)?
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis() as u64)
+        .unwrap_or(0);
+    Ok(NoteVersion {
+        modified,
+        hash: content_hash(&fs::read(path)?),
+    })
+}
+
+/// the first 16 bytes of the sha256 of the content, in hex
+pub(crate) fn content_hash(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .take(16)
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// the content on disk is still the expected version.
+/// A file which was removed is not a conflict, since saving only writes it again.
+pub(crate) fn check_version(path: &Path, expected: &NoteVersion) -> io::Result<bool> {
+    match note_version(path) {
+        Ok(current) => Ok(!expected.is_modified(&current)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(true),
+        Err(e) => Err(e),
+    }
+}
+
+/// Write the file only if it is still the expected version on disk, false when it was modified.
+/// The file is written regardless when there is no expected version.
+pub(crate) fn write_if_unmodified(
+    path: &Path,
+    content: &[u8],
+    expected: Option<&NoteVersion>,
+) -> io::Result<bool> {
+    // the lock only guards the file system, so it is still usable if a write panicked
+    let _guard = WRITE_LOCK
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if let Some(expected) = expected {
+        if !check_version(path, expected)? {
+            return Ok(false);
+        }
+    }
+    fs::write(path, content)?;
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modified_file_is_not_overwritten() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("note.md");
+        fs::write(&path, "loaded").unwrap();
+        let loaded = note_version(&path).unwrap();
+        assert!(write_if_unmodified(&path, b"saved", Some(&loaded)).unwrap());
+
+        // the version loaded before the save is no longer the one on disk
+        assert!(!write_if_unmodified(&path, b"stale", Some(&loaded)).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "saved");
+        assert!(write_if_unmodified(&path, b"forced", None).unwrap());
+        assert_eq!(fs::read_to_string(&path).unwrap(), "forced");
+    }
+}
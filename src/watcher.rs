@@ -0,0 +1,67 @@
+use crate::workspace::Workspace;
+use client::NoteEvent;
+use notify::{DebouncedEvent, RecursiveMode, Watcher};
+use std::path::Path;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+use tokio::sync::broadcast;
+
+/// events are coalesced within this duration
+const DEBOUNCE_DELAY: Duration = Duration::from_millis(200);
+
+/// Watch the workspace for modified notes, the events are sent to the returned channel.
+///
+/// The watcher runs in its own thread for as long as the process lives.
+pub(crate) fn watch(
+    workspace: &Workspace,
+    capacity: usize,
+) -> notify::Result<broadcast::Sender<NoteEvent>> {
+    let (events_tx, _events_rx) = broadcast::channel(capacity);
+    let (watcher_tx, watcher_rx) = mpsc::channel();
+    let mut watcher = notify::watcher(watcher_tx, DEBOUNCE_DELAY)?;
+    watcher.watch(workspace.root(), RecursiveMode::Recursive)?;
+
+    let workspace = workspace.clone();
+    let sender = events_tx.clone();
+    thread::spawn(move || {
+        // moved into the thread, the watching stops when it is dropped
+        let _watcher = watcher;
+        while let Ok(event) = watcher_rx.recv() {
+            if let Some(note_event) = to_note_event(&workspace, event) {
+                // it is an error only when there are no subscribers
+                sender.send(note_event).ok();
+            }
+        }
+    });
+    Ok(events_tx)
+}
+
+fn to_note_event(workspace: &Workspace, event: DebouncedEvent) -> Option<NoteEvent> {
+    match event {
+        DebouncedEvent::Create(path)
+        | DebouncedEvent::Write(path)
+        | DebouncedEvent::Rename(_, path) => {
+            let note = note_path(workspace, &path)?;
+            let content = workspace.read_note(&note).ok()?;
+            Some(NoteEvent::Changed {
+                path: note,
+                content,
+            })
+        }
+        DebouncedEvent::Remove(path) => {
+            let note = note_path(workspace, &path)?;
+            Some(NoteEvent::Removed { path: note })
+        }
+        _ => None,
+    }
+}
+
+/// the path relative to the workspace, only if it is a note
+fn note_path(workspace: &Workspace, path: &Path) -> Option<String> {
+    if crate::workspace::is_note(path) {
+        workspace.relative_path(path)
+    } else {
+        None
+    }
+}
@@ -0,0 +1,132 @@
+//! The geometry of the window of the desktop app, which is restored on the next launch
+use crate::config::config_dir;
+pub(crate) use native::NativeWindow;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+
+mod native;
+
+/// the size of the window on the first launch
+pub(crate) const DEFAULT_SIZE: (i32, i32) = (1200, 600);
+
+/// windows smaller than this are not restored, since they are hard to find and resize
+const MIN_SIZE: i32 = 200;
+
+/// the part of the top of the window which must be on a screen, so it can still be dragged
+const GRIP: i32 = 50;
+
+/// The position and size of the window, in screen pixels
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub(crate) struct WindowGeometry {
+    pub(crate) x: i32,
+    pub(crate) y: i32,
+    pub(crate) width: i32,
+    pub(crate) height: i32,
+}
+
+fn geometry_file() -> Option<PathBuf> {
+    config_dir().map(|dir| dir.join("window.json"))
+}
+
+/// the geometry of the window when the app was last closed
+pub(crate) fn load() -> Option<WindowGeometry> {
+    let json = fs::read_to_string(geometry_file()?).ok()?;
+    let geometry: WindowGeometry = serde_json::from_str(&json).ok()?;
+    if geometry.width >= MIN_SIZE && geometry.height >= MIN_SIZE {
+        Some(geometry)
+    } else {
+        None
+    }
+}
+
+/// failing to store the geometry is not fatal, the default size is used on the next launch
+pub(crate) fn store(geometry: &WindowGeometry) {
+    if let Err(e) = write(geometry) {
+        eprintln!("unable to store the window geometry: {}", e);
+    }
+}
+
+fn write(geometry: &WindowGeometry) -> io::Result<()> {
+    let file = geometry_file()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "there is no config directory"))?;
+    if let Some(parent) = file.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(file, serde_json::to_string_pretty(geometry)?)
+}
+
+/// Keep the window on the available screens, since the screen it was on may be disconnected.
+/// A window whose top is not on any screen is centered on the first one, shrunk to fit it.
+pub(crate) fn clamp(geometry: WindowGeometry, screens: &[WindowGeometry]) -> WindowGeometry {
+    let is_on_screen = screens.iter().any(|screen| {
+        let left = geometry.x.max(screen.x);
+        let right = (geometry.x + geometry.width).min(screen.x + screen.width);
+        right - left >= GRIP
+            && geometry.y >= screen.y
+            && geometry.y + GRIP <= screen.y + screen.height
+    });
+    match screens.first() {
+        Some(screen) if !is_on_screen => {
+            let width = geometry.width.min(screen.width);
+            let height = geometry.height.min(screen.height);
+            WindowGeometry {
+                x: screen.x + (screen.width - width) / 2,
+                y: screen.y + (screen.height - height) / 2,
+                width,
+                height,
+            }
+        }
+        _ => geometry,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn geometry(x: i32, y: i32, width: i32, height: i32) -> WindowGeometry {
+        WindowGeometry {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    #[test]
+    fn window_on_a_screen_is_kept() {
+        let screens = [geometry(0, 0, 1920, 1080), geometry(1920, 0, 1280, 1024)];
+        let window = geometry(2000, 100, 800, 600);
+        assert_eq!(clamp(window, &screens), window);
+        // partly off the screen, but its top can still be dragged
+        let window = geometry(-700, 100, 800, 600);
+        assert_eq!(clamp(window, &screens), window);
+    }
+
+    #[test]
+    fn window_off_the_screens_is_centered() {
+        // the second screen was disconnected
+        let screens = [geometry(0, 0, 1920, 1080)];
+        assert_eq!(
+            clamp(geometry(2000, 100, 800, 600), &screens),
+            geometry(560, 240, 800, 600)
+        );
+        // the title bar is above the screen
+        assert_eq!(
+            clamp(geometry(100, -20, 800, 600), &screens),
+            geometry(560, 240, 800, 600)
+        );
+        assert_eq!(
+            clamp(geometry(3000, 0, 2560, 1440), &screens),
+            geometry(0, 0, 1920, 1080)
+        );
+    }
+
+    #[test]
+    fn window_is_kept_without_screens() {
+        let window = geometry(2000, 100, 800, 600);
+        assert_eq!(clamp(window, &[]), window);
+    }
+}
@@ -0,0 +1,227 @@
+//! The native window of the webview, which the webview can only set the size of.
+//! It is moved and measured through the windowing system, on gtk and windows.
+use super::WindowGeometry;
+use web_view::WebView;
+
+/// The top level window of the webview.
+/// It can only be used while the window is open, that is until `WebView::step` returns None.
+pub(crate) struct NativeWindow(platform::Handle);
+
+impl NativeWindow {
+    /// None when the window can not be moved on this platform
+    pub(crate) fn of<T>(webview: &WebView<'_, T>) -> Option<Self> {
+        platform::handle(webview).map(Self)
+    }
+
+    pub(crate) fn geometry(&self) -> Option<WindowGeometry> {
+        platform::geometry(&self.0)
+    }
+
+    /// the areas of the connected screens which are not covered by the panels
+    pub(crate) fn screens(&self) -> Vec<WindowGeometry> {
+        platform::screens(&self.0)
+    }
+
+    pub(crate) fn set_geometry(&self, geometry: &WindowGeometry) {
+        platform::set_geometry(&self.0, geometry)
+    }
+}
+
+#[cfg(all(target_family = "unix", not(target_os = "macos")))]
+mod platform {
+    use super::WindowGeometry;
+    use gdk_sys::{
+        gdk_display_get_monitor, gdk_display_get_n_monitors, gdk_monitor_get_workarea, GdkRectangle,
+    };
+    use glib_sys::g_list_free;
+    use gtk_sys::{
+        gtk_widget_get_display, gtk_window_get_position, gtk_window_get_size,
+        gtk_window_get_window_type, gtk_window_list_toplevels, gtk_window_move, gtk_window_resize,
+        GtkWidget, GtkWindow, GTK_WINDOW_TOPLEVEL,
+    };
+    use std::os::raw::c_int;
+    use web_view::WebView;
+
+    pub(super) struct Handle(*mut GtkWindow);
+
+    /// The window handle of the webview is not implemented on gtk, but its window is the only
+    /// top level window right after it is built.
+    pub(super) fn handle<T>(_webview: &WebView<'_, T>) -> Option<Handle> {
+        unsafe {
+            let toplevels = gtk_window_list_toplevels();
+            let mut item = toplevels;
+            let mut window = None;
+            while !item.is_null() {
+                let toplevel = (*item).data as *mut GtkWindow;
+                if gtk_window_get_window_type(toplevel) == GTK_WINDOW_TOPLEVEL {
+                    window = Some(Handle(toplevel));
+                    break;
+                }
+                item = (*item).next;
+            }
+            g_list_free(toplevels);
+            window
+        }
+    }
+
+    /// the position of the frame and the size of the content, which is what the builder sets
+    pub(super) fn geometry(handle: &Handle) -> Option<WindowGeometry> {
+        let (mut x, mut y, mut width, mut height): (c_int, c_int, c_int, c_int) = (0, 0, 0, 0);
+        unsafe {
+            gtk_window_get_position(handle.0, &mut x, &mut y);
+            gtk_window_get_size(handle.0, &mut width, &mut height);
+        }
+        Some(WindowGeometry {
+            x,
+            y,
+            width,
+            height,
+        })
+    }
+
+    pub(super) fn screens(handle: &Handle) -> Vec<WindowGeometry> {
+        unsafe {
+            let display = gtk_widget_get_display(handle.0 as *mut GtkWidget);
+            if display.is_null() {
+                return vec![];
+            }
+            (0..gdk_display_get_n_monitors(display))
+                .map(|i| gdk_display_get_monitor(display, i))
+                .filter(|monitor| !monitor.is_null())
+                .map(|monitor| {
+                    let mut area = GdkRectangle {
+                        x: 0,
+                        y: 0,
+                        width: 0,
+                        height: 0,
+                    };
+                    gdk_monitor_get_workarea(monitor, &mut area);
+                    WindowGeometry {
+                        x: area.x,
+                        y: area.y,
+                        width: area.width,
+                        height: area.height,
+                    }
+                })
+                .collect()
+        }
+    }
+
+    pub(super) fn set_geometry(handle: &Handle, geometry: &WindowGeometry) {
+        unsafe {
+            gtk_window_move(handle.0, geometry.x, geometry.y);
+            gtk_window_resize(handle.0, geometry.width, geometry.height);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod platform {
+    use super::WindowGeometry;
+    use std::mem;
+    use std::ptr;
+    use web_view::WebView;
+    use winapi::shared::minwindef::{BOOL, LPARAM, TRUE};
+    use winapi::shared::windef::{HDC, HMONITOR, HWND, LPRECT, RECT};
+    use winapi::um::winuser::{
+        EnumDisplayMonitors, GetMonitorInfoW, GetWindowRect, SetWindowPos, MONITORINFO,
+        SWP_NOACTIVATE, SWP_NOZORDER,
+    };
+
+    pub(super) struct Handle(HWND);
+
+    pub(super) fn handle<T>(webview: &WebView<'_, T>) -> Option<Handle> {
+        let hwnd = webview.window_handle() as HWND;
+        if hwnd.is_null() {
+            None
+        } else {
+            Some(Handle(hwnd))
+        }
+    }
+
+    /// the outer bounds of the window, in physical pixels
+    pub(super) fn geometry(handle: &Handle) -> Option<WindowGeometry> {
+        let mut rect: RECT = unsafe { mem::zeroed() };
+        if unsafe { GetWindowRect(handle.0, &mut rect) } == 0 {
+            return None;
+        }
+        Some(rect_geometry(&rect))
+    }
+
+    pub(super) fn screens(_handle: &Handle) -> Vec<WindowGeometry> {
+        let mut screens: Vec<WindowGeometry> = vec![];
+        unsafe {
+            EnumDisplayMonitors(
+                ptr::null_mut(),
+                ptr::null(),
+                Some(push_screen),
+                &mut screens as *mut Vec<WindowGeometry> as LPARAM,
+            );
+        }
+        screens
+    }
+
+    unsafe extern "system" fn push_screen(
+        monitor: HMONITOR,
+        _hdc: HDC,
+        _clip: LPRECT,
+        screens: LPARAM,
+    ) -> BOOL {
+        let screens = &mut *(screens as *mut Vec<WindowGeometry>);
+        let mut info: MONITORINFO = mem::zeroed();
+        info.cbSize = mem::size_of::<MONITORINFO>() as u32;
+        if GetMonitorInfoW(monitor, &mut info) != 0 {
+            screens.push(rect_geometry(&info.rcWork));
+        }
+        TRUE
+    }
+
+    /// the size of the builder is the size of the content, so the outer bounds are set here
+    pub(super) fn set_geometry(handle: &Handle, geometry: &WindowGeometry) {
+        unsafe {
+            SetWindowPos(
+                handle.0,
+                ptr::null_mut(),
+                geometry.x,
+                geometry.y,
+                geometry.width,
+                geometry.height,
+                SWP_NOZORDER | SWP_NOACTIVATE,
+            );
+        }
+    }
+
+    fn rect_geometry(rect: &RECT) -> WindowGeometry {
+        WindowGeometry {
+            x: rect.left,
+            y: rect.top,
+            width: rect.right - rect.left,
+            height: rect.bottom - rect.top,
+        }
+    }
+}
+
+/// The window is neither moved nor measured on the other platforms, it opens with the default size
+#[cfg(not(any(windows, all(target_family = "unix", not(target_os = "macos")))))]
+mod platform {
+    use super::WindowGeometry;
+    use web_view::WebView;
+
+    pub(super) enum Handle {}
+
+    pub(super) fn handle<T>(_webview: &WebView<'_, T>) -> Option<Handle> {
+        None
+    }
+
+    pub(super) fn geometry(handle: &Handle) -> Option<WindowGeometry> {
+        match *handle {}
+    }
+
+    pub(super) fn screens(handle: &Handle) -> Vec<WindowGeometry> {
+        match *handle {}
+    }
+
+    pub(super) fn set_geometry(handle: &Handle, _geometry: &WindowGeometry) {
+        match *handle {}
+    }
+}
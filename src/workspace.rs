@@ -0,0 +1,250 @@
+use crate::server::ServiceError;
+use crate::version;
+use client::{links, NoteEntry, NoteVersion};
+use percent_encoding::percent_decode_str;
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+/// the file extensions which are recognized as notes
+const NOTE_EXTENSIONS: [&str; 2] = ["md", "markdown"];
+
+/// The workspace is the root directory where all the notes and the files they referenced
+/// reside. Any path that resolves outside of this directory is refused.
+#[derive(Clone, Debug)]
+pub struct Workspace {
+    root: PathBuf,
+}
+
+impl Workspace {
+    /// create a workspace rooted at this directory, the directory must exist
+    pub fn new(root: impl AsRef<Path>) -> io::Result<Self> {
+        let root = root.as_ref().canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("{} is not a directory", root.display()),
+            ));
+        }
+        Ok(Self { root })
+    }
+
+    /// use the current directory as the workspace
+    pub fn current_dir() -> io::Result<Self> {
+        Self::new(std::env::current_dir()?)
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Resolve a path relative to the workspace root into an absolute path.
+    ///
+    /// `..` are resolved lexically and it is an error to go above the workspace root.
+    /// Symlinks are followed on the part of the path that already exists,
+    /// so a link pointing outside of the workspace is also refused.
+    pub fn resolve(&self, relative: &str) -> Result<PathBuf, ServiceError> {
+        let mut normalized = PathBuf::new();
+        for component in Path::new(relative.trim_start_matches('/')).components() {
+            match component {
+                Component::Normal(part) => normalized.push(part),
+                Component::CurDir => (),
+                Component::ParentDir => {
+                    if !normalized.pop() {
+                        return Err(ServiceError::Forbidden(relative.to_string()));
+                    }
+                }
+                Component::RootDir | Component::Prefix(_) => {
+                    return Err(ServiceError::Forbidden(relative.to_string()))
+                }
+            }
+        }
+        let path = self.root.join(&normalized);
+
+        // the deepest ancestor that exists, this is at least the workspace root
+        let existing = path
+            .ancestors()
+            .find(|ancestor| ancestor.exists())
+            .unwrap_or(&self.root);
+        let canonical = existing
+            .canonicalize()
+            .map_err(|e| ServiceError::IoError(e.to_string()))?;
+
+        if canonical.starts_with(&self.root) {
+            Ok(path)
+        } else {
+            Err(ServiceError::Forbidden(relative.to_string()))
+        }
+    }
+
+    /// the path of this absolute path relative to the workspace, separated with `/`
+    pub fn relative_path(&self, path: &Path) -> Option<String> {
+        let relative = path.strip_prefix(&self.root).ok()?;
+        let parts: Vec<String> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy().to_string())
+            .collect();
+        Some(parts.join("/"))
+    }
+
+    /// The file a link or an image of the note refers to, relative to the workspace.
+    /// It is None for the destinations which are not local files, such as `/absolute` paths,
+    /// urls or `#fragment`, and it is an error if it resolves outside of the workspace.
+    /// The destination is a url, so it is percent decoded, ie: `my%20plot.png` is `my plot.png`.
+    pub fn linked_path(&self, note: &str, dest: &str) -> Result<Option<String>, ServiceError> {
+        if !links::is_local(dest) {
+            return Ok(None);
+        }
+        let (path, _fragment) = links::split_fragment(dest);
+        if path.is_empty() {
+            return Ok(None);
+        }
+        let note_dir = Path::new(note)
+            .parent()
+            .map(|dir| dir.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let path = percent_decode_str(path).decode_utf8_lossy();
+        let file = self.resolve(&format!("{}/{}", note_dir, path))?;
+        self.relative_path(&file)
+            .map(Some)
+            .ok_or_else(|| ServiceError::Forbidden(dest.to_string()))
+    }
+
+    /// list all the notes in the workspace, hidden files and directories are skipped
+    pub fn list_notes(&self) -> Result<Vec<NoteEntry>, ServiceError> {
+        let mut notes = vec![];
+        for path in self.list_files()? {
+            if is_note(&path) {
+                if let Some(relative) = self.relative_path(&path) {
+                    notes.push(NoteEntry {
+                        path: relative,
+                        size: fs::metadata(&path)?.len(),
+                    });
+                }
+            }
+        }
+        notes.sort_by(|a, b| a.path.cmp(&b.path));
+        Ok(notes)
+    }
+
+    /// list all the files in the workspace, hidden files and directories are skipped
+    pub fn list_files(&self) -> Result<Vec<PathBuf>, ServiceError> {
+        let mut files = vec![];
+        collect_files(&self.root, &mut files)?;
+        Ok(files)
+    }
+
+    pub fn read_note(&self, relative: &str) -> Result<String, ServiceError> {
+        let path = self.resolve_note(relative)?;
+        if !path.is_file() {
+            return Err(ServiceError::NotFound);
+        }
+        Ok(fs::read_to_string(path)?)
+    }
+
+    /// the version of the note on disk
+    pub fn note_version(&self, relative: &str) -> Result<NoteVersion, ServiceError> {
+        let path = self.resolve_note(relative)?;
+        Ok(version::note_version(&path)?)
+    }
+
+    /// write the note only if it is still the expected version on disk
+    pub fn write_note_if(
+        &self,
+        relative: &str,
+        content: &str,
+        expected: &NoteVersion,
+    ) -> Result<(), ServiceError> {
+        let path = self.resolve_note(relative)?;
+        if path.is_dir() {
+            return Err(ServiceError::Conflict(relative.to_string()));
+        }
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if version::write_if_unmodified(&path, content.as_bytes(), Some(expected))? {
+            Ok(())
+        } else {
+            Err(ServiceError::PreconditionFailed(relative.to_string()))
+        }
+    }
+
+    /// create a new note, it is an error if the note already exists
+    pub fn create_note(&self, relative: &str, content: &str) -> Result<(), ServiceError> {
+        let path = self.resolve_note(relative)?;
+        if path.exists() {
+            return Err(ServiceError::Conflict(relative.to_string()));
+        }
+        self.write_file(&path, content)
+    }
+
+    /// write the content of the note, creating it if it doesn't exist yet
+    pub fn write_note(&self, relative: &str, content: &str) -> Result<(), ServiceError> {
+        let path = self.resolve_note(relative)?;
+        if path.is_dir() {
+            return Err(ServiceError::Conflict(relative.to_string()));
+        }
+        self.write_file(&path, content)
+    }
+
+    pub fn delete_note(&self, relative: &str) -> Result<(), ServiceError> {
+        let path = self.resolve_note(relative)?;
+        if !path.is_file() {
+            return Err(ServiceError::NotFound);
+        }
+        Ok(fs::remove_file(path)?)
+    }
+
+    fn write_file(&self, path: &Path, content: &str) -> Result<(), ServiceError> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(fs::write(path, content)?)
+    }
+
+    /// resolve the path and make sure it is a markdown file
+    fn resolve_note(&self, relative: &str) -> Result<PathBuf, ServiceError> {
+        let path = self.resolve(relative)?;
+        if is_note(&path) {
+            Ok(path)
+        } else {
+            Err(ServiceError::BadRequest(format!(
+                "{} is not a markdown file",
+                relative
+            )))
+        }
+    }
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), ServiceError> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            collect_files(&entry.path(), files)?;
+        } else if file_type.is_file() {
+            files.push(entry.path());
+        }
+    }
+    Ok(())
+}
+
+/// hidden files such as `.git` and `.madnotes` are not part of the notes
+pub(crate) fn is_hidden(relative: &str) -> bool {
+    Path::new(relative)
+        .components()
+        .any(|component| match component {
+            Component::Normal(part) => part.to_string_lossy().starts_with('.'),
+            _ => false,
+        })
+}
+
+pub(crate) fn is_note(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| NOTE_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+        .unwrap_or(false)
+}